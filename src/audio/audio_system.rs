@@ -1,8 +1,27 @@
 use std::error::Error;
 
+/// Volume at which streams play unamplified, as shown by pavucontrol
+pub const NORMAL_VOLUME_PERCENT: f32 = 100.0;
+/// Highest volume reachable once boosting is allowed, the pavucontrol slider limit
+pub const MAX_BOOST_VOLUME_PERCENT: f32 = 150.0;
+
+/// An output (sink) or input (source) device
+#[derive(Debug, Clone, PartialEq)]
+pub struct AudioDevice {
+    pub index: u32,
+    /// Name the sound server knows the device by, stable across restarts unlike the index
+    pub name: String,
+    pub description: String,
+    pub icon_name: Option<String>,
+    pub is_default: bool,
+}
+
 #[derive(Debug)]
 pub struct AppInfo {
     pub uid: u32,
+    /// Stable key of the app the stream belongs to, see `identity::resolve_app_id`.
+    /// Devices use their name.
+    pub app_id: String,
     pub app_name: String,
     pub sink_name: Option<String>,
     pub mute: bool,
@@ -10,26 +29,112 @@ pub struct AppInfo {
     pub icon_name: Option<String>,
     pub is_device: bool,
     pub is_multi_sink_app: bool,
+    /// Recording stream (source output) or input device instead of playback
+    pub is_input: bool,
+    /// Output device a playback stream currently plays to, if known
+    pub output_device: Option<AudioDevice>,
+    /// Stream its app has paused (corked), it stays around without playing anything
+    pub is_paused: bool,
+    /// Other streams of the same app merged into this one when streams are grouped
+    pub grouped_uids: Vec<u32>,
+    /// Executable of the app behind a stream, if it tells
+    pub process_binary: Option<String>,
+    /// What a stream is for (e.g. music, phone, event), if it tells
+    pub media_role: Option<String>,
+}
+
+impl AppInfo {
+    /// Whether an entry of the ignore list or a pin refers to this app. Entries made before
+    /// apps had an identity hold the app name instead.
+    pub fn is_named(&self, name: &str) -> bool {
+        self.app_id == name || self.app_name == name
+    }
+
+    /// A playing playback stream of `app_id` at full volume
+    #[cfg(test)]
+    pub fn test_stream(uid: u32, app_id: &str) -> Self {
+        AppInfo {
+            uid,
+            app_id: app_id.to_string(),
+            app_name: app_id.to_string(),
+            sink_name: None,
+            mute: false,
+            vol_percent: NORMAL_VOLUME_PERCENT,
+            icon_name: None,
+            is_device: false,
+            is_multi_sink_app: false,
+            is_input: false,
+            output_device: None,
+            is_paused: false,
+            grouped_uids: Vec::new(),
+            process_binary: None,
+            media_role: None,
+        }
+    }
 }
 
 pub trait AudioSystem {
     fn list_applications(&mut self) -> Result<Vec<AppInfo>, Box<dyn Error>>;
+    /// A single stream or device as `list_applications` would list it, used to update
+    /// one channel on a change event. `is_multi_sink_app` is not worked out here.
+    /// Backends that can look one up directly override this.
+    fn get_application(
+        &mut self,
+        app_index: u32,
+        is_device: bool,
+        is_input: bool,
+    ) -> Result<Option<AppInfo>, Box<dyn Error>> {
+        Ok(self.list_applications()?.into_iter().find(|app| {
+            app.uid == app_index && app.is_device == is_device && app.is_input == is_input
+        }))
+    }
     fn increase_volume(
         &mut self,
         app_index: u32,
         percent: f64,
         is_device: bool,
+        is_input: bool,
     ) -> Result<(), Box<dyn Error>>;
     fn decrease_volume(
         &mut self,
         app_index: u32,
         percent: f64,
         is_device: bool,
+        is_input: bool,
     ) -> Result<(), Box<dyn Error>>;
     fn mute_volume(
         &mut self,
         app_index: u32,
         mute: bool,
         is_device: bool,
+        is_input: bool,
+    ) -> Result<(), Box<dyn Error>>;
+    /// Set an absolute volume in percent, 100 being unamplified. Channel balance is kept.
+    fn set_volume(
+        &mut self,
+        app_index: u32,
+        percent: f32,
+        is_device: bool,
+        is_input: bool,
+    ) -> Result<(), Box<dyn Error>>;
+    /// Current volume in percent, as reported in `AppInfo::vol_percent`
+    fn get_volume(
+        &mut self,
+        app_index: u32,
+        is_device: bool,
+        is_input: bool,
+    ) -> Result<f32, Box<dyn Error>>;
+    fn list_output_devices(&mut self) -> Result<Vec<AudioDevice>, Box<dyn Error>>;
+    /// Microphones and other capture devices, without monitors of output devices
+    fn list_input_devices(&mut self) -> Result<Vec<AudioDevice>, Box<dyn Error>>;
+    /// Make a device the default output or input, optionally moving the streams
+    /// currently playing or recording elsewhere onto it
+    fn set_default_device(
+        &mut self,
+        device_index: u32,
+        is_input: bool,
+        move_streams: bool,
     ) -> Result<(), Box<dyn Error>>;
+    /// Route a playback stream to another output device
+    fn move_to_sink(&mut self, app_index: u32, sink_index: u32) -> Result<(), Box<dyn Error>>;
 }
@@ -0,0 +1,208 @@
+use std::collections::HashMap;
+use std::fs;
+use std::sync::{LazyLock, Mutex};
+
+/// Identity of streams that tell nothing about the app behind them
+pub const UNKNOWN_APP_ID: &str = "app_stream";
+
+// Keys the pid of a stream's process is found under. PipeWire sets the first from the
+// socket credentials, which is the real pid even for sandboxed apps that report their own.
+const PID_KEYS: [&str; 2] = ["pipewire.sec.pid", "application.process.id"];
+
+// Processes looked up so far, pids get reused so the cache is dropped once it grows past this
+const MAX_CACHED_PROCESSES: usize = 256;
+
+static PROCESSES: LazyLock<Mutex<HashMap<u32, ProcessIdentity>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// What the process behind a stream tells about its app
+#[derive(Clone, Debug, Default)]
+struct ProcessIdentity {
+    flatpak_app_id: Option<String>,
+    snap_name: Option<String>,
+    process_name: Option<String>,
+}
+
+impl ProcessIdentity {
+    fn read(pid: u32) -> Self {
+        Self {
+            flatpak_app_id: flatpak_app_id(pid),
+            snap_name: snap_name(pid),
+            process_name: process_name(pid),
+        }
+    }
+}
+
+/// Stable key for the app behind a stream, worked out from the stream's properties.
+/// Sandboxed apps go by their Flatpak or Snap id and other apps by their executable,
+/// so streams that are unnamed (or renamed on every track) still map to their app.
+/// Ignoring, pinning and grouping go by this key.
+pub fn resolve_app_id(prop: impl Fn(&str) -> Option<String>) -> String {
+    resolve_app_id_with(prop, cached_process)
+}
+
+fn resolve_app_id_with(
+    prop: impl Fn(&str) -> Option<String>,
+    process: impl Fn(u32) -> ProcessIdentity,
+) -> String {
+    let pid = PID_KEYS
+        .iter()
+        .find_map(|key| prop(key)?.trim().parse::<u32>().ok());
+    let process = pid.map(process).unwrap_or_default();
+
+    prop("application.id")
+        .or_else(|| prop("pipewire.access.portal.app_id"))
+        .or(process.flatpak_app_id)
+        .or(process.snap_name)
+        .or_else(|| prop("application.process.binary"))
+        .or(process.process_name)
+        .or_else(|| prop("application.name"))
+        .or_else(|| prop("media.role").map(|role| format!("role:{}", role)))
+        .map(|id| id.trim().to_lowercase())
+        .filter(|id| !id.is_empty())
+        .unwrap_or_else(|| UNKNOWN_APP_ID.to_string())
+}
+
+/// Reads /proc once per process, every stream of an app resolves again on each refresh
+fn cached_process(pid: u32) -> ProcessIdentity {
+    let mut processes = PROCESSES.lock().unwrap();
+    if let Some(process) = processes.get(&pid) {
+        return process.clone();
+    }
+
+    if processes.len() >= MAX_CACHED_PROCESSES {
+        processes.clear();
+    }
+    let process = ProcessIdentity::read(pid);
+    processes.insert(pid, process.clone());
+    process
+}
+
+/// App id from the `[Application]` section of a Flatpak sandbox's info file
+fn flatpak_app_id(pid: u32) -> Option<String> {
+    let info = fs::read_to_string(format!("/proc/{}/root/.flatpak-info", pid)).ok()?;
+
+    let mut in_application = false;
+    for line in info.lines().map(str::trim) {
+        if line.starts_with('[') {
+            in_application = line == "[Application]";
+        } else if in_application && let Some(name) = line.strip_prefix("name=") {
+            return Some(name.to_string());
+        }
+    }
+
+    None
+}
+
+/// Snap name from the `snap.<name>.<app>` cgroup every snap app runs in
+fn snap_name(pid: u32) -> Option<String> {
+    let cgroups = fs::read_to_string(format!("/proc/{}/cgroup", pid)).ok()?;
+
+    cgroups
+        .lines()
+        .filter_map(|line| line.split('/').next_back())
+        .find_map(|unit| unit.strip_prefix("snap.")?.split('.').next())
+        .filter(|name| !name.is_empty())
+        .map(str::to_string)
+}
+
+/// Executable name the kernel knows the process by
+fn process_name(pid: u32) -> Option<String> {
+    fs::read_to_string(format!("/proc/{}/comm", pid))
+        .ok()
+        .map(|comm| comm.trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn resolve(props: &HashMap<&str, &str>, process: &ProcessIdentity) -> String {
+        resolve_app_id_with(
+            |key| props.get(key).map(|value| value.to_string()),
+            |_| process.clone(),
+        )
+    }
+
+    #[test]
+    fn identity_sources_win_in_priority_order() {
+        let mut props = HashMap::from([
+            ("application.process.id", "42"),
+            ("application.id", "org.app.Id"),
+            ("pipewire.access.portal.app_id", "org.portal.App"),
+            ("application.process.binary", "binary"),
+            ("application.name", "Name"),
+            ("media.role", "music"),
+        ]);
+        let mut process = ProcessIdentity {
+            flatpak_app_id: Some("org.flatpak.App".to_string()),
+            snap_name: Some("snap".to_string()),
+            process_name: Some("comm".to_string()),
+        };
+
+        assert_eq!(resolve(&props, &process), "org.app.id");
+        props.remove("application.id");
+        assert_eq!(resolve(&props, &process), "org.portal.app");
+        props.remove("pipewire.access.portal.app_id");
+        assert_eq!(resolve(&props, &process), "org.flatpak.app");
+        process.flatpak_app_id = None;
+        assert_eq!(resolve(&props, &process), "snap");
+        process.snap_name = None;
+        assert_eq!(resolve(&props, &process), "binary");
+        props.remove("application.process.binary");
+        assert_eq!(resolve(&props, &process), "comm");
+        process.process_name = None;
+        assert_eq!(resolve(&props, &process), "name");
+        props.remove("application.name");
+        assert_eq!(resolve(&props, &process), "role:music");
+        props.remove("media.role");
+        assert_eq!(resolve(&props, &process), UNKNOWN_APP_ID);
+    }
+
+    #[test]
+    fn process_is_only_asked_with_a_pid() {
+        let props = HashMap::from([("application.name", "Name")]);
+        let process = ProcessIdentity {
+            process_name: Some("comm".to_string()),
+            ..Default::default()
+        };
+
+        assert_eq!(resolve(&props, &process), "name");
+    }
+
+    #[test]
+    fn pid_from_the_socket_wins_over_the_reported_one() {
+        let props = HashMap::from([("pipewire.sec.pid", "7"), ("application.process.id", "2")]);
+        let app_id = resolve_app_id_with(
+            |key| props.get(key).map(|value| value.to_string()),
+            |pid| ProcessIdentity {
+                process_name: Some(format!("pid{}", pid)),
+                ..Default::default()
+            },
+        );
+
+        assert_eq!(app_id, "pid7");
+    }
+
+    #[test]
+    fn unnamed_streams_of_different_apps_stay_apart() {
+        let props = HashMap::from([("application.process.id", "42")]);
+        let first = resolve(
+            &props,
+            &ProcessIdentity {
+                process_name: Some("mpv".to_string()),
+                ..Default::default()
+            },
+        );
+        let second = resolve(
+            &props,
+            &ProcessIdentity {
+                process_name: Some("vlc".to_string()),
+                ..Default::default()
+            },
+        );
+
+        assert_eq!(first, "mpv");
+        assert_eq!(second, "vlc");
+    }
+}
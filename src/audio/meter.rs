@@ -0,0 +1,308 @@
+use super::{AppInfo, Backend, backend, monitor};
+use crate::{mixer, utils};
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{LazyLock, Mutex};
+use std::time::{Duration, Instant};
+
+/// How much of the shown level is kept on the next update, so meters fall back smoothly
+const LEVEL_FALLOFF: f32 = 0.6;
+/// Levels are shown in steps, so small wobbles don't redraw the keys
+const LEVEL_STEPS: f32 = 16.0;
+/// Peaks up to this (about -60 dB) count as silence
+const SILENCE_THRESHOLD: f32 = 0.001;
+
+static METERS_STARTED: AtomicBool = AtomicBool::new(false);
+
+// Whether the backend can sample peaks, silence can't be told without
+static SAMPLING_SUPPORTED: AtomicBool = AtomicBool::new(false);
+
+// Playback streams to sample: stream index -> name of the output device it plays to
+static WATCHED_STREAMS: LazyLock<Mutex<HashMap<u32, String>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+// Loudest peak (0.0 - 1.0) of every sampled stream since the meters were last drawn
+static PEAKS: LazyLock<Mutex<HashMap<u32, f32>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// What is known about whether a playback stream is playing anything
+struct StreamActivity {
+    /// Output device the stream plays to, needed to sample it
+    device_name: Option<String>,
+    paused: bool,
+    last_active: Instant,
+    /// When a peak over `SILENCE_THRESHOLD` was last sampled, if ever
+    last_sound: Option<Instant>,
+}
+
+/// Whether a playback stream plays anything, as far as the meters can tell
+#[derive(Clone, Copy, Debug)]
+pub struct Activity {
+    pub paused: bool,
+    /// Whether the stream is sampled, silence is only known for sampled streams
+    pub sampled: bool,
+    pub last_sound: Option<Instant>,
+}
+
+// Every playback stream the sound server listed, to hide the ones that stay paused or silent
+static ACTIVITY: LazyLock<Mutex<HashMap<u32, StreamActivity>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+// Playback streams that have been paused or silent for too long to be shown
+static INACTIVE_STREAMS: LazyLock<Mutex<HashSet<u32>>> =
+    LazyLock::new(|| Mutex::new(HashSet::new()));
+
+// Playback streams to sample whether they are shown or not
+static KEPT_STREAMS: LazyLock<Mutex<HashSet<u32>>> = LazyLock::new(|| Mutex::new(HashSet::new()));
+
+/// Streams the backend sampler should measure, with the output device each plays to
+pub fn watched_streams() -> HashMap<u32, String> {
+    WATCHED_STREAMS.lock().unwrap().clone()
+}
+
+/// Called by the backend sampler with every peak it measured for a stream
+pub fn report_peak(uid: u32, peak: f32) {
+    let mut peaks = PEAKS.lock().unwrap();
+    let entry = peaks.entry(uid).or_insert(0.0);
+    *entry = entry.max(peak.clamp(0.0, 1.0));
+    drop(peaks);
+
+    if peak > SILENCE_THRESHOLD
+        && let Some(stream) = ACTIVITY.lock().unwrap().get_mut(&uid)
+    {
+        let now = Instant::now();
+        stream.last_active = now;
+        stream.last_sound = Some(now);
+    }
+}
+
+/// Keep up with the playback streams the sound server lists. When `all_streams` is set
+/// the list is complete and streams missing from it are forgotten.
+pub fn track_streams(applications: &[AppInfo], all_streams: bool) {
+    let streams: Vec<&AppInfo> = applications
+        .iter()
+        .filter(|app| !app.is_device && !app.is_input)
+        .collect();
+
+    let mut activity = ACTIVITY.lock().unwrap();
+    if all_streams {
+        activity.retain(|uid, _| streams.iter().any(|app| app.uid == *uid));
+    }
+
+    for app in streams {
+        let stream = activity.entry(app.uid).or_insert_with(|| StreamActivity {
+            device_name: None,
+            paused: false,
+            last_active: Instant::now(),
+            last_sound: None,
+        });
+        stream.device_name = app.output_device.as_ref().map(|device| device.name.clone());
+        stream.paused = app.is_paused;
+    }
+}
+
+/// Whether a playback stream is hidden for being paused or silent for too long
+pub fn is_inactive(app: &AppInfo) -> bool {
+    !app.is_device && !app.is_input && INACTIVE_STREAMS.lock().unwrap().contains(&app.uid)
+}
+
+/// Whether a playback stream the sound server listed plays anything
+pub fn stream_activity(uid: u32) -> Option<Activity> {
+    let sampling = SAMPLING_SUPPORTED.load(Ordering::Acquire);
+    ACTIVITY.lock().unwrap().get(&uid).map(|stream| Activity {
+        paused: stream.paused,
+        sampled: sampling && stream.device_name.is_some(),
+        last_sound: stream.last_sound,
+    })
+}
+
+/// Sample these playback streams too, whether they are shown or not
+pub fn keep_sampling(uids: HashSet<u32>) {
+    *KEPT_STREAMS.lock().unwrap() = uids;
+}
+
+/// Starts the backend sampler and redraws the meters at the configured rate.
+/// Nothing is sampled while meters are turned off.
+pub fn start_level_meters() {
+    if METERS_STARTED.swap(true, Ordering::AcqRel) {
+        return; // Already started
+    }
+
+    let supported = match backend() {
+        Backend::PulseAudio => {
+            super::pulse::start_pulse_meters();
+            true
+        }
+        #[cfg(feature = "pipewire")]
+        Backend::PipeWire => {
+            println!("Level meters are not supported by the PipeWire backend");
+            false
+        }
+        Backend::Simulated => {
+            super::simulated::start_simulated_meters();
+            true
+        }
+    };
+    SAMPLING_SUPPORTED.store(supported, Ordering::Release);
+
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(redraw_interval(utils::get_level_meter_rate())).await;
+
+            let inactive_changed = update_inactive_streams();
+
+            utils::update_channel_keys(&update_levels().await).await;
+
+            // Streams going quiet or playing again move apps on or off the deck
+            if inactive_changed {
+                println!("Paused or silent apps changed");
+                monitor::request_refresh();
+            }
+        }
+    });
+}
+
+/// Time between two meter redraws at `rate` redraws per second
+fn redraw_interval(rate: u8) -> Duration {
+    Duration::from_millis(1000 / rate.max(1) as u64)
+}
+
+/// Level to show for a meter that showed `shown` and got `peak` since. Meters fall back
+/// smoothly and move in steps, so small wobbles don't redraw the keys.
+fn next_level(shown: Option<f32>, peak: f32) -> f32 {
+    let shown = shown.unwrap_or(0.0) * LEVEL_FALLOFF;
+    (peak.max(shown) * LEVEL_STEPS).floor() / LEVEL_STEPS
+}
+
+/// Work out which streams have been paused or silent for too long, returns whether that changed
+fn update_inactive_streams() -> bool {
+    let now = Instant::now();
+    let hiding = utils::should_hide_inactive_apps() && utils::is_audio_connected();
+    let sampling = SAMPLING_SUPPORTED.load(Ordering::Acquire);
+
+    let mut activity = ACTIVITY.lock().unwrap();
+
+    for stream in activity.values_mut() {
+        // Silence is only known for sampled streams, the others play unless paused.
+        // Streams nothing looks out for aren't sampled, they start counting once something does.
+        let sampled = sampling && stream.device_name.is_some();
+        if !hiding || (!stream.paused && !sampled) {
+            stream.last_active = now;
+        }
+    }
+
+    let inactive: HashSet<u32> = if hiding {
+        let timeout = utils::get_inactive_timeout();
+        activity
+            .iter()
+            .filter(|(_, stream)| now.duration_since(stream.last_active) >= timeout)
+            .map(|(&uid, _)| uid)
+            .collect()
+    } else {
+        HashSet::new()
+    };
+    drop(activity);
+
+    let mut current = INACTIVE_STREAMS.lock().unwrap();
+    if *current == inactive {
+        return false;
+    }
+    *current = inactive;
+    true
+}
+
+/// Feed the sampled peaks to the channels, returns the slots whose meter changed
+async fn update_levels() -> Vec<u8> {
+    let enabled = utils::should_show_level_meters() && utils::is_audio_connected();
+    let peaks = std::mem::take(&mut *PEAKS.lock().unwrap());
+
+    let mut channels = mixer::MIXER_CHANNELS.lock().await;
+    let activity = ACTIVITY.lock().unwrap();
+    let mut watched = HashMap::new();
+    let mut changed = Vec::new();
+
+    for (&slot, channel) in channels.iter_mut() {
+        let device = channel
+            .output_device
+            .as_ref()
+            .filter(|_| enabled && !channel.is_placeholder && !channel.is_device);
+
+        let level = match device {
+            Some(device) => {
+                // Grouped streams can play to other devices than the one shown
+                let mut peak: f32 = 0.0;
+                for uid in channel.stream_uids() {
+                    let device_name = activity
+                        .get(&uid)
+                        .and_then(|stream| stream.device_name.clone())
+                        .unwrap_or_else(|| device.name.clone());
+                    watched.insert(uid, device_name);
+                    peak = peak.max(peaks.get(&uid).copied().unwrap_or(0.0));
+                }
+
+                Some(next_level(channel.level, peak))
+            }
+            None => None,
+        };
+
+        if channel.level != level {
+            channel.level = level;
+            changed.push(slot);
+        }
+    }
+
+    // Streams that could be hidden are sampled too, whether they are shown or not
+    if utils::should_hide_inactive_apps() && utils::is_audio_connected() {
+        for (&uid, stream) in activity.iter() {
+            if let Some(device_name) = &stream.device_name {
+                watched.entry(uid).or_insert_with(|| device_name.clone());
+            }
+        }
+    }
+
+    // Streams others look out for, e.g. calls to tell when they play
+    for uid in KEPT_STREAMS.lock().unwrap().iter() {
+        if let Some(device_name) = activity
+            .get(uid)
+            .and_then(|stream| stream.device_name.clone())
+        {
+            watched.entry(*uid).or_insert(device_name);
+        }
+    }
+
+    drop(activity);
+
+    *WATCHED_STREAMS.lock().unwrap() = watched;
+    changed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn meters_redraw_at_the_configured_rate() {
+        assert_eq!(redraw_interval(10), Duration::from_millis(100));
+        assert_eq!(redraw_interval(30), Duration::from_millis(33));
+        assert_eq!(redraw_interval(0), Duration::from_secs(1));
+    }
+
+    #[test]
+    fn levels_move_in_steps_and_fall_back_smoothly() {
+        // Small wobbles within a step leave the shown level alone
+        assert_eq!(next_level(None, 0.5), 0.5);
+        assert_eq!(next_level(None, 0.52), 0.5);
+
+        // Louder peaks show right away, quiet ones let the level fall back bit by bit
+        assert_eq!(next_level(Some(0.5), 1.0), 1.0);
+        let falling = next_level(Some(1.0), 0.0);
+        assert_eq!(falling, 0.5625);
+        assert!(next_level(Some(falling), 0.0) < falling);
+
+        // Silence reaches zero eventually
+        let mut level = 1.0;
+        for _ in 0..10 {
+            level = next_level(Some(level), 0.0);
+        }
+        assert_eq!(level, 0.0);
+    }
+}
@@ -1,9 +1,129 @@
 pub mod audio_system;
+pub mod identity;
+pub mod meter;
+pub mod monitor;
+#[cfg(feature = "pipewire")]
+pub mod pipewire;
 pub mod pulse;
+pub mod simulated;
+pub mod worker;
 
-pub use audio_system::{AppInfo, AudioSystem};
+pub use audio_system::{
+    AppInfo, AudioDevice, AudioSystem, MAX_BOOST_VOLUME_PERCENT, NORMAL_VOLUME_PERCENT,
+};
+#[cfg(feature = "pipewire")]
+pub use pipewire::PipeWireAudioSystem;
 pub use pulse::PulseAudioSystem;
+pub use simulated::SimulatedAudioSystem;
+pub use worker::{request, submit};
 
-pub fn create() -> Box<dyn AudioSystem> {
-    Box::new(PulseAudioSystem::new().unwrap())
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+/// CLI flag used to pick the audio backend, e.g. `--audio-backend pipewire`
+const BACKEND_CLI_FLAG: &str = "--audio-backend";
+/// Environment variable used to pick the audio backend when the CLI flag is absent
+const BACKEND_ENV_VAR: &str = "OA_VOLUME_CONTROLLER_AUDIO_BACKEND";
+
+static BACKEND: OnceLock<Backend> = OnceLock::new();
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backend {
+    PulseAudio,
+    #[cfg(feature = "pipewire")]
+    PipeWire,
+    /// In-memory fake apps and devices, for demos and running without a sound server
+    Simulated,
+}
+
+impl Default for Backend {
+    fn default() -> Self {
+        // Building with the pipewire feature means the native backend is wanted
+        #[cfg(feature = "pipewire")]
+        return Backend::PipeWire;
+        #[cfg(not(feature = "pipewire"))]
+        return Backend::PulseAudio;
+    }
+}
+
+impl FromStr for Backend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "pulse" | "pulseaudio" => Ok(Backend::PulseAudio),
+            #[cfg(feature = "pipewire")]
+            "pipewire" | "pw" => Ok(Backend::PipeWire),
+            #[cfg(not(feature = "pipewire"))]
+            "pipewire" | "pw" => Err("the PipeWire backend was not compiled in \
+                 (build with --features pipewire)"
+                .to_string()),
+            "simulated" | "sim" => Ok(Backend::Simulated),
+            other => Err(format!("unknown audio backend '{}'", other)),
+        }
+    }
+}
+
+impl fmt::Display for Backend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Backend::PulseAudio => write!(f, "PulseAudio"),
+            #[cfg(feature = "pipewire")]
+            Backend::PipeWire => write!(f, "PipeWire"),
+            Backend::Simulated => write!(f, "simulated audio"),
+        }
+    }
+}
+
+/// Picks the backend from the CLI flag, then the environment, then the build default.
+/// Only the first call has an effect.
+pub fn select_backend(args: &[String]) -> Backend {
+    *BACKEND.get_or_init(|| {
+        let requested = args
+            .iter()
+            .position(|arg| arg == BACKEND_CLI_FLAG)
+            .and_then(|i| args.get(i + 1).cloned())
+            .or_else(|| std::env::var(BACKEND_ENV_VAR).ok());
+
+        match requested {
+            Some(name) => name.parse().unwrap_or_else(|e| {
+                eprintln!("{}, using {}", e, Backend::default());
+                Backend::default()
+            }),
+            None => Backend::default(),
+        }
+    })
+}
+
+pub fn backend() -> Backend {
+    *BACKEND.get_or_init(Backend::default)
+}
+
+/// Connects to the selected backend. Only the audio worker holds a connection,
+/// everything else goes through `request` and `submit`.
+fn connect() -> Result<Box<dyn AudioSystem>, Box<dyn Error>> {
+    println!("Connecting to {}...", backend());
+
+    Ok(match backend() {
+        Backend::PulseAudio => Box::new(PulseAudioSystem::new()?),
+        #[cfg(feature = "pipewire")]
+        Backend::PipeWire => Box::new(PipeWireAudioSystem::new()?),
+        Backend::Simulated => Box::new(SimulatedAudioSystem::new()?),
+    })
+}
+
+/// Starts listening for changes on the selected backend and refreshing the mixer on them
+pub fn start_monitoring() {
+    monitor::start_refresh_processor();
+    meter::start_level_meters();
+    crate::ducking::start_call_watch();
+
+    match backend() {
+        Backend::PulseAudio => pulse::start_pulse_monitoring(),
+        #[cfg(feature = "pipewire")]
+        Backend::PipeWire => pipewire::start_pipewire_monitoring(),
+        Backend::Simulated => simulated::start_simulated_monitoring(),
+    }
 }
@@ -0,0 +1,326 @@
+use crate::{audio, ducking, mixer, restore, utils};
+use std::collections::HashSet;
+use std::error::Error;
+use std::sync::LazyLock;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+/// First wait before reconnecting to a sound server that went away, doubled on every failed attempt
+const INITIAL_RECONNECT_DELAY: Duration = Duration::from_millis(500);
+/// Longest wait between reconnection attempts
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(30);
+
+/// What kind of object a change event is about
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EventFacility {
+    /// An app playing audio (sink input)
+    PlaybackStream,
+    /// An app recording audio (source output)
+    RecordingStream,
+    /// A sink
+    OutputDevice,
+    /// A source
+    InputDevice,
+}
+
+impl EventFacility {
+    fn is_device(self) -> bool {
+        matches!(
+            self,
+            EventFacility::OutputDevice | EventFacility::InputDevice
+        )
+    }
+
+    fn is_input(self) -> bool {
+        matches!(
+            self,
+            EventFacility::RecordingStream | EventFacility::InputDevice
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EventKind {
+    New,
+    Changed,
+    Removed,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AudioEvent {
+    /// Something changed that needs the whole mixer rebuilt, e.g. the default device
+    Refresh,
+    /// A stream or device appeared, changed or went away
+    Object {
+        facility: EventFacility,
+        kind: EventKind,
+        index: u32,
+    },
+}
+
+type RefreshChannel = (
+    mpsc::UnboundedSender<AudioEvent>,
+    std::sync::Mutex<Option<mpsc::UnboundedReceiver<AudioEvent>>>,
+);
+
+// Global channel for audio events, fed by whichever backend monitor is running
+static REFRESH_CHANNEL: LazyLock<RefreshChannel> = LazyLock::new(|| {
+    let (tx, rx) = mpsc::unbounded_channel();
+    (tx, std::sync::Mutex::new(Some(rx)))
+});
+
+// Set when the next refresh has to start over from a freshly (re)started sound server
+static RESYNC_REQUESTED: AtomicBool = AtomicBool::new(false);
+
+// Bumped on every reconnection, connections opened before it are stale
+static CONNECTION_GENERATION: AtomicU64 = AtomicU64::new(0);
+
+/// Ask for the mixer to be rebuilt from the audio system. Safe to call from any thread.
+pub fn request_refresh() {
+    let _ = REFRESH_CHANNEL.0.send(AudioEvent::Refresh);
+}
+
+/// Report a change of a single stream or device. Changes to streams and devices that are
+/// on a channel only update that channel, anything else rebuilds the mixer.
+/// Safe to call from any thread.
+pub fn send_event(facility: EventFacility, kind: EventKind, index: u32) {
+    let _ = REFRESH_CHANNEL.0.send(AudioEvent::Object {
+        facility,
+        kind,
+        index,
+    });
+}
+
+/// Keeps a backend monitor alive. `session` connects to the sound server and only
+/// returns once the connection is gone (or could not be made), it is then started
+/// again after an exponential backoff. The deck shows the disconnected state meanwhile.
+pub fn supervise(server_name: &str, mut session: impl FnMut() -> Result<(), Box<dyn Error>>) {
+    let mut delay = None;
+
+    loop {
+        if let Err(e) = session() {
+            eprintln!("{} monitoring stopped: {}", server_name, e);
+        }
+
+        let was_connected = utils::is_audio_connected();
+        if was_connected {
+            utils::set_audio_connected(false);
+            request_refresh();
+        }
+
+        let wait = reconnect_delay(delay, was_connected);
+        println!("Reconnecting to {} in {:?}", server_name, wait);
+        std::thread::sleep(wait);
+        delay = Some(wait);
+    }
+}
+
+/// Wait before the next reconnection attempt, given the `previous` wait if there was one.
+/// Waits double up to the longest, a session that got connected before losing the server
+/// starts over with short waits.
+fn reconnect_delay(previous: Option<Duration>, was_connected: bool) -> Duration {
+    match previous {
+        Some(previous) if !was_connected => (previous * 2).min(MAX_RECONNECT_DELAY),
+        _ => INITIAL_RECONNECT_DELAY,
+    }
+}
+
+/// Called by a monitor session once it is connected and subscribed. Stream and device
+/// indices start over when the server restarts, so the whole mixer is resynced.
+pub fn connection_established() {
+    if !utils::is_audio_connected() {
+        CONNECTION_GENERATION.fetch_add(1, Ordering::AcqRel);
+        RESYNC_REQUESTED.store(true, Ordering::Release);
+        utils::set_audio_connected(true);
+    }
+    request_refresh();
+}
+
+/// Changes whenever the sound server was reconnected to
+pub fn connection_generation() -> u64 {
+    CONNECTION_GENERATION.load(Ordering::Acquire)
+}
+
+pub fn start_refresh_processor() {
+    // Take the receiver from the global channel
+    let receiver = REFRESH_CHANNEL.1.lock().unwrap().take();
+
+    if let Some(mut receiver) = receiver {
+        tokio::spawn(async move {
+            loop {
+                // Wait for first event
+                let Some(event) = receiver.recv().await else {
+                    break;
+                };
+
+                // Debounce: wait 100ms and collect all pending events
+                tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+
+                let mut events = HashSet::from([event]);
+                while let Ok(event) = receiver.try_recv() {
+                    events.insert(event);
+                }
+
+                // Restarted apps get their volume back before they show up on the deck
+                let new_streams: Vec<u32> = events
+                    .iter()
+                    .filter_map(|event| match *event {
+                        AudioEvent::Object {
+                            facility: EventFacility::PlaybackStream,
+                            kind: EventKind::New,
+                            index,
+                        } if utils::is_audio_connected() => Some(index),
+                        _ => None,
+                    })
+                    .collect();
+                if !new_streams.is_empty() {
+                    restore::restore_volumes(new_streams).await;
+                }
+
+                // Streams or devices coming and going can move apps between slots
+                let changed: Option<Vec<(EventFacility, u32)>> = events
+                    .into_iter()
+                    .map(|event| match event {
+                        AudioEvent::Object {
+                            facility,
+                            kind: EventKind::Changed,
+                            index,
+                        } => Some((facility, index)),
+                        _ => None,
+                    })
+                    .collect();
+
+                let result = match changed {
+                    Some(changed) => refresh_changed_channels(changed).await,
+                    None => {
+                        println!("Processing debounced refresh request...");
+                        refresh_audio_applications().await
+                    }
+                };
+
+                if let Err(e) = result {
+                    eprintln!("Failed to refresh audio applications: {:?}", e);
+                }
+            }
+        });
+    }
+}
+
+/// Update only the channels showing the changed streams and devices, and only redraw
+/// their keys. Falls back to a full refresh when a change can move a channel.
+async fn refresh_changed_channels(
+    changed: Vec<(EventFacility, u32)>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if !utils::is_audio_connected() || RESYNC_REQUESTED.load(Ordering::Acquire) {
+        return refresh_audio_applications().await;
+    }
+
+    for (facility, index) in changed {
+        let (is_device, is_input) = (facility.is_device(), facility.is_input());
+
+        // Changes to streams that are not on a channel (e.g. ignored apps) don't matter
+        if mixer::find_channel(index, is_device, is_input)
+            .await
+            .is_none()
+        {
+            continue;
+        }
+
+        let app = audio::request(move |audio_system| {
+            audio_system.get_application(index, is_device, is_input)
+        })
+        .await;
+
+        let update = match app {
+            Ok(Some(app)) => {
+                audio::meter::track_streams(std::slice::from_ref(&app), false);
+                ducking::update_ducking(std::slice::from_ref(&app), false).await;
+                restore::remember_volumes(std::slice::from_ref(&app), false).await;
+                mixer::update_mixer_channel(app).await
+            }
+            // Gone in the meantime, the removal event rebuilds the mixer
+            _ => mixer::ChannelUpdate::NeedsRefresh,
+        };
+
+        match update {
+            mixer::ChannelUpdate::Unchanged => {}
+            mixer::ChannelUpdate::Updated(slot) => utils::update_channel_keys(&[slot]).await,
+            mixer::ChannelUpdate::NeedsRefresh => return refresh_audio_applications().await,
+        }
+    }
+
+    Ok(())
+}
+
+pub async fn refresh_audio_applications() -> Result<(), Box<dyn std::error::Error>> {
+    // Keep the channels (and so the columns apps are on) until the server is back
+    if !utils::is_audio_connected() {
+        utils::update_stream_deck_buttons().await;
+        utils::update_default_device_keys().await;
+        return Ok(());
+    }
+
+    if RESYNC_REQUESTED.swap(false, Ordering::AcqRel) {
+        println!("Resyncing mixer with the sound server...");
+        mixer::forget_streams().await;
+        restore::forget_streams();
+        ducking::forget_streams();
+    }
+
+    // Get current applications (same logic as manual-detection)
+    let applications = audio::request(|audio_system| audio_system.list_applications())
+        .await
+        .map_err(|e| format!("Error fetching applications: {}", e))?;
+    audio::meter::track_streams(&applications, true);
+    ducking::update_ducking(&applications, true).await;
+    restore::remember_volumes(&applications, true).await;
+
+    // Get ignored apps list and filter rules from shared settings
+    let filter = {
+        let settings = crate::plugin::SHARED_SETTINGS.lock().await;
+        mixer::AppFilter::new(
+            &settings.ignored_apps_list,
+            &settings.app_filters,
+            settings.filter_mode,
+        )
+    };
+
+    let pinned_apps = crate::plugin::get_pinned_slots().await;
+
+    // Update mixers and Stream Deck buttons
+    mixer::update_mixer_channels(applications, &filter, &pinned_apps).await;
+    utils::update_stream_deck_buttons().await;
+    utils::update_default_device_keys().await;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reconnect_waits_double_up_to_the_longest() {
+        let mut delay = None;
+        let mut waits = Vec::new();
+        for _ in 0..9 {
+            let wait = reconnect_delay(delay, false);
+            waits.push(wait.as_millis());
+            delay = Some(wait);
+        }
+        assert_eq!(
+            waits,
+            [500, 1000, 2000, 4000, 8000, 16000, 30000, 30000, 30000]
+        );
+    }
+
+    #[test]
+    fn reconnect_starts_over_after_being_connected() {
+        assert_eq!(
+            reconnect_delay(Some(MAX_RECONNECT_DELAY), true),
+            INITIAL_RECONNECT_DELAY
+        );
+        assert_eq!(reconnect_delay(None, true), INITIAL_RECONNECT_DELAY);
+    }
+}
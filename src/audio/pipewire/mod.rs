@@ -0,0 +1,6 @@
+#[allow(clippy::module_inception)]
+pub mod pipewire;
+pub mod pipewire_monitor;
+
+pub use pipewire::PipeWireAudioSystem;
+pub use pipewire_monitor::start_pipewire_monitoring;
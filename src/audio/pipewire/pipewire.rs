@@ -0,0 +1,858 @@
+use crate::audio::{
+    AppInfo, AudioDevice, AudioSystem, MAX_BOOST_VOLUME_PERCENT, identity::resolve_app_id,
+};
+use ::pipewire as pw;
+use pw::{
+    context::Context,
+    core::Core,
+    main_loop::MainLoop,
+    metadata::{Metadata, MetadataListener},
+    node::{Node, NodeListener, NodeState},
+    registry::{GlobalObject, Listener as RegistryListener, Registry},
+    spa::{
+        param::ParamType,
+        pod::{
+            Object, Pod, Property, Value, ValueArray, deserialize::PodDeserializer,
+            serialize::PodSerializer,
+        },
+        utils::{SpaTypes, dict::DictRef},
+    },
+    types::ObjectType,
+};
+use std::{
+    cell::{Cell, RefCell},
+    collections::{BTreeMap, HashMap},
+    error::Error,
+    io::Cursor,
+    rc::Rc,
+};
+
+pub(super) const DEFAULT_METADATA_NAME: &str = "default";
+pub(super) const DEFAULT_SINK_KEY: &str = "default.audio.sink";
+pub(super) const DEFAULT_SOURCE_KEY: &str = "default.audio.source";
+/// Metadata key the session manager reads to route a stream to a specific node
+const TARGET_OBJECT_KEY: &str = "target.object";
+// Keys the session manager persists the user's choice of default devices under
+const CONFIGURED_DEFAULT_SINK_KEY: &str = "default.configured.audio.sink";
+const CONFIGURED_DEFAULT_SOURCE_KEY: &str = "default.configured.audio.source";
+
+/// The kind of audio nodes the mixer cares about, keyed on `media.class`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum NodeClass {
+    /// An application playing audio (what PulseAudio calls a sink input)
+    Stream,
+    /// An output device
+    Sink,
+    /// An application recording audio (what PulseAudio calls a source output)
+    Recorder,
+    /// An input device, e.g. a microphone
+    Source,
+}
+
+impl NodeClass {
+    pub(super) fn from_media_class(media_class: Option<&str>) -> Option<Self> {
+        match media_class? {
+            "Stream/Output/Audio" => Some(NodeClass::Stream),
+            "Audio/Sink" => Some(NodeClass::Sink),
+            "Stream/Input/Audio" => Some(NodeClass::Recorder),
+            "Audio/Source" => Some(NodeClass::Source),
+            _ => None,
+        }
+    }
+
+    pub(super) fn is_input(&self) -> bool {
+        matches!(self, NodeClass::Recorder | NodeClass::Source)
+    }
+
+    pub(super) fn is_device(&self) -> bool {
+        matches!(self, NodeClass::Sink | NodeClass::Source)
+    }
+}
+
+/// Channel volumes and mute state read from a node's Props param
+#[derive(Debug, Clone, Default)]
+struct NodeVolume {
+    channel_volumes: Vec<f32>,
+    mute: bool,
+}
+
+/// A node bound on the worker connection, its listener keeps `volume`, `running` and
+/// `media_name` current
+struct AudioNode {
+    proxy: Node,
+    _listener: NodeListener,
+    class: NodeClass,
+    name: Option<String>,
+    serial: Option<String>,
+    description: Option<String>,
+    /// Stable key of the app behind a stream, see `resolve_app_id`
+    app_id: String,
+    process_binary: Option<String>,
+    media_role: Option<String>,
+    app_name: Option<String>,
+    media_name: Option<String>,
+    icon_name: Option<String>,
+    volume: NodeVolume,
+    /// Whether the node is processing audio, streams their app paused are idle
+    running: bool,
+}
+
+/// The metadata object holding the default devices, also used to route streams
+struct DefaultMetadata {
+    id: u32,
+    proxy: Metadata,
+    _listener: MetadataListener,
+}
+
+/// Everything the registry announced that the mixer cares about. The registry and proxy
+/// listeners keep it current whenever the main loop runs, i.e. during roundtrips.
+#[derive(Default)]
+struct Graph {
+    nodes: BTreeMap<u32, AudioNode>,
+    default_sink: Option<String>,
+    default_source: Option<String>,
+    /// (output node, input node) of every link by link id, used to find the sink a stream plays to
+    links: HashMap<u32, (u32, u32)>,
+    default_metadata: Option<DefaultMetadata>,
+    /// Whether objects were bound since the last roundtrip, their state comes with the next one
+    has_new_proxies: bool,
+}
+
+pub struct PipeWireAudioSystem {
+    graph: Rc<RefCell<Graph>>,
+    // Field order matters: proxies and listeners must be dropped before the connection they live on
+    _registry_listener: RegistryListener,
+    _registry: Rc<Registry>,
+    core: Core,
+    _context: Context,
+    mainloop: MainLoop,
+}
+
+impl PipeWireAudioSystem {
+    pub fn new() -> Result<Self, Box<dyn Error>> {
+        let mainloop = MainLoop::new(None)?;
+        let context = Context::new(&mainloop)?;
+        let core = context.connect(None)?;
+        let registry = Rc::new(core.get_registry()?);
+        let graph: Rc<RefCell<Graph>> = Rc::default();
+
+        // The registry announces every existing global once and then only what changes,
+        // so it stays alive for as long as the connection does
+        let registry_listener = registry
+            .add_listener_local()
+            .global({
+                let registry = Rc::downgrade(&registry);
+                let graph = Rc::downgrade(&graph);
+                move |global| {
+                    let (Some(registry), Some(graph)) = (registry.upgrade(), graph.upgrade())
+                    else {
+                        return;
+                    };
+
+                    if let Err(e) = add_global(&registry, &graph, global) {
+                        eprintln!("Failed to bind PipeWire object {}: {}", global.id, e);
+                    }
+                }
+            })
+            .global_remove({
+                let graph = Rc::downgrade(&graph);
+                move |id| {
+                    if let Some(graph) = graph.upgrade() {
+                        graph.borrow_mut().remove(id);
+                    }
+                }
+            })
+            .register();
+
+        Ok(Self {
+            graph,
+            _registry_listener: registry_listener,
+            _registry: registry,
+            core,
+            _context: context,
+            mainloop,
+        })
+    }
+
+    /// Runs the main loop until the server has processed everything sent so far
+    fn roundtrip(&self) -> Result<(), Box<dyn Error>> {
+        let done = Rc::new(Cell::new(false));
+        let error = Rc::new(RefCell::new(None));
+        let pending = self.core.sync(0)?;
+
+        let _listener = self
+            .core
+            .add_listener_local()
+            .done({
+                let done = done.clone();
+                let mainloop = self.mainloop.clone();
+                move |id, seq| {
+                    if id == pw::core::PW_ID_CORE && seq == pending {
+                        done.set(true);
+                        mainloop.quit();
+                    }
+                }
+            })
+            .error({
+                let error = error.clone();
+                let mainloop = self.mainloop.clone();
+                move |id, _seq, _res, message| {
+                    if id == pw::core::PW_ID_CORE {
+                        *error.borrow_mut() = Some(message.to_string());
+                        mainloop.quit();
+                    }
+                }
+            })
+            .register();
+
+        while !done.get() {
+            if let Some(message) = error.borrow_mut().take() {
+                return Err(format!("PipeWire error: {}", message).into());
+            }
+            self.mainloop.run();
+        }
+
+        Ok(())
+    }
+
+    /// Applies what changed on the server since the last call. Objects that appeared in
+    /// the meantime are bound on the way and report their state with another roundtrip.
+    fn sync_nodes(&self) -> Result<(), Box<dyn Error>> {
+        loop {
+            self.roundtrip()?;
+            if !std::mem::take(&mut self.graph.borrow_mut().has_new_proxies) {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Sends a Props param to a node, the graph must not be borrowed while this runs
+    fn set_props(&self, id: u32, property: Property) -> Result<(), Box<dyn Error>> {
+        let props = Value::Object(Object {
+            type_: SpaTypes::ObjectParamProps.as_raw(),
+            id: ParamType::Props.as_raw(),
+            properties: vec![property],
+        });
+        let bytes = PodSerializer::serialize(Cursor::new(Vec::new()), &props)
+            .map_err(|e| format!("Failed to serialize Props: {:?}", e))?
+            .0
+            .into_inner();
+        let pod = Pod::from_bytes(&bytes).ok_or("Failed to build Props pod")?;
+
+        self.graph
+            .borrow()
+            .get_node(id)?
+            .proxy
+            .set_param(ParamType::Props, 0, pod);
+        self.roundtrip()
+    }
+
+    fn set_channel_volumes(
+        &self,
+        id: u32,
+        channel_volumes: Vec<f32>,
+    ) -> Result<(), Box<dyn Error>> {
+        if channel_volumes.is_empty() {
+            return Err(format!("PipeWire node {} has no channel volumes", id).into());
+        }
+
+        self.set_props(
+            id,
+            Property::new(
+                pw::spa::sys::SPA_PROP_channelVolumes,
+                Value::ValueArray(ValueArray::Float(channel_volumes)),
+            ),
+        )
+    }
+
+    fn change_volume(&mut self, id: u32, delta: f32) -> Result<(), Box<dyn Error>> {
+        self.sync_nodes()?;
+        let channel_volumes = step_channel_volumes(
+            &self.graph.borrow().get_node(id)?.volume.channel_volumes,
+            delta,
+        );
+        self.set_channel_volumes(id, channel_volumes)
+    }
+}
+
+/// Binds a global the mixer cares about and keeps its proxy in the graph
+fn add_global(
+    registry: &Registry,
+    graph: &Rc<RefCell<Graph>>,
+    global: &GlobalObject<&DictRef>,
+) -> Result<(), Box<dyn Error>> {
+    let prop = |key: &str| global.props.and_then(|props| props.get(key));
+    let id = global.id;
+
+    match global.type_ {
+        ObjectType::Metadata if prop("metadata.name") == Some(DEFAULT_METADATA_NAME) => {
+            let proxy: Metadata = registry.bind(global)?;
+            let listener = proxy
+                .add_listener_local()
+                .property({
+                    let graph = Rc::downgrade(graph);
+                    move |_subject, key, _type, value| {
+                        let Some(graph) = graph.upgrade() else {
+                            return 0;
+                        };
+                        let mut graph = graph.borrow_mut();
+                        match key {
+                            Some(DEFAULT_SINK_KEY) => {
+                                graph.default_sink = value.and_then(parse_node_name);
+                            }
+                            Some(DEFAULT_SOURCE_KEY) => {
+                                graph.default_source = value.and_then(parse_node_name);
+                            }
+                            _ => {}
+                        }
+                        0
+                    }
+                })
+                .register();
+
+            let mut graph = graph.borrow_mut();
+            graph.default_metadata = Some(DefaultMetadata {
+                id,
+                proxy,
+                _listener: listener,
+            });
+            graph.has_new_proxies = true;
+        }
+        ObjectType::Node => {
+            let Some(class) = NodeClass::from_media_class(prop("media.class")) else {
+                return Ok(());
+            };
+
+            let proxy: Node = registry.bind(global)?;
+            let listener = proxy
+                .add_listener_local()
+                .info({
+                    let graph = Rc::downgrade(graph);
+                    move |info| {
+                        let Some(graph) = graph.upgrade() else {
+                            return;
+                        };
+                        let mut graph = graph.borrow_mut();
+                        let Some(node) = graph.nodes.get_mut(&id) else {
+                            return;
+                        };
+
+                        node.running = matches!(info.state(), NodeState::Running);
+                        // Players retitle their stream with every track
+                        if let Some(media_name) =
+                            info.props().and_then(|props| props.get("media.name"))
+                        {
+                            node.media_name = Some(media_name.to_string());
+                        }
+                    }
+                })
+                .param({
+                    let graph = Rc::downgrade(graph);
+                    move |_seq, _id, _index, _next, param| {
+                        let Some(graph) = graph.upgrade() else {
+                            return;
+                        };
+                        if let Some(volume) = param.and_then(parse_props)
+                            && let Some(node) = graph.borrow_mut().nodes.get_mut(&id)
+                        {
+                            node.volume = volume;
+                        }
+                    }
+                })
+                .register();
+            // Reports the current volume now and every change after
+            proxy.subscribe_params(&[ParamType::Props]);
+
+            let owned_prop = |key: &str| prop(key).map(str::to_string);
+            let node = AudioNode {
+                proxy,
+                _listener: listener,
+                class,
+                name: owned_prop("node.name"),
+                serial: owned_prop("object.serial"),
+                description: owned_prop("node.description"),
+                app_id: resolve_app_id(&owned_prop),
+                process_binary: owned_prop("application.process.binary"),
+                media_role: owned_prop("media.role"),
+                app_name: owned_prop("application.name"),
+                media_name: owned_prop("media.name"),
+                icon_name: owned_prop("application.icon_name")
+                    .or_else(|| owned_prop("device.icon-name")),
+                volume: NodeVolume::default(),
+                running: true,
+            };
+
+            let mut graph = graph.borrow_mut();
+            graph.nodes.insert(id, node);
+            graph.has_new_proxies = true;
+        }
+        ObjectType::Link => {
+            let node_id = |key: &str| prop(key)?.parse::<u32>().ok();
+            if let (Some(output), Some(input)) =
+                (node_id("link.output.node"), node_id("link.input.node"))
+            {
+                graph.borrow_mut().links.insert(id, (output, input));
+            }
+        }
+        _ => {}
+    }
+
+    Ok(())
+}
+
+impl Graph {
+    /// Drops whatever the removed global was, proxies included
+    fn remove(&mut self, id: u32) {
+        self.nodes.remove(&id);
+        self.links.remove(&id);
+        if self
+            .default_metadata
+            .as_ref()
+            .is_some_and(|metadata| metadata.id == id)
+        {
+            self.default_metadata = None;
+            self.default_sink = None;
+            self.default_source = None;
+        }
+    }
+
+    fn devices(&self, is_input: bool) -> Vec<AudioDevice> {
+        let (class, default_device, fallback_name) = if is_input {
+            (NodeClass::Source, &self.default_source, "Microphone")
+        } else {
+            (NodeClass::Sink, &self.default_sink, "Output")
+        };
+
+        self.nodes
+            .iter()
+            .filter(|(_, node)| node.class == class)
+            .map(|(&id, node)| AudioDevice {
+                index: id,
+                name: node.name.clone().unwrap_or_default(),
+                description: node
+                    .description
+                    .clone()
+                    .or(node.name.clone())
+                    .unwrap_or(fallback_name.to_string()),
+                icon_name: node.icon_name.clone(),
+                is_default: node.name.is_some() && &node.name == default_device,
+            })
+            .collect()
+    }
+
+    fn default_metadata(&self) -> Result<&Metadata, Box<dyn Error>> {
+        self.default_metadata
+            .as_ref()
+            .map(|metadata| &metadata.proxy)
+            .ok_or_else(|| "No default metadata to route streams with".into())
+    }
+
+    /// The sink a playback stream is linked to
+    fn get_output_device(&self, id: u32, output_devices: &[AudioDevice]) -> Option<AudioDevice> {
+        self.links
+            .values()
+            .filter(|(output, _)| *output == id)
+            .find_map(|(_, input)| output_devices.iter().find(|device| device.index == *input))
+            .cloned()
+    }
+
+    /// Playback or recording streams, optionally led by the default device of that direction
+    fn list_channels(&self, is_input: bool, with_default_device: bool) -> Vec<AppInfo> {
+        let mut res: Vec<AppInfo> = Vec::new();
+
+        let streams: Vec<(&u32, &AudioNode)> = self
+            .nodes
+            .iter()
+            .filter(|(_, node)| !node.class.is_device() && node.class.is_input() == is_input)
+            .collect();
+
+        // Collect all app names including the device if present
+        let mut app_names: Vec<String> = streams
+            .iter()
+            .map(|(_, node)| stream_app_name(node))
+            .collect();
+
+        let (default_device, fallback_name, icon_name) = if is_input {
+            (&self.default_source, "Microphone", "audio-input-microphone")
+        } else {
+            (&self.default_sink, "System Audio", "audio-card")
+        };
+
+        if with_default_device
+            && let Some((&id, device)) = self.nodes.iter().find(|(_, node)| {
+                node.class.is_device()
+                    && node.class.is_input() == is_input
+                    && node.name.is_some()
+                    && &node.name == default_device
+            })
+        {
+            let device_name = device
+                .description
+                .clone()
+                .unwrap_or(fallback_name.to_string());
+
+            // Add device name to app_names for duplicate detection
+            app_names.push(device_name.clone());
+
+            res.push(AppInfo {
+                uid: id,
+                app_id: device.name.clone().unwrap_or(device_name.clone()),
+                app_name: device_name,
+                sink_name: Some(fallback_name.to_string()),
+                mute: device.volume.mute,
+                vol_percent: get_volume_percentage(&device.volume.channel_volumes),
+                icon_name: device.icon_name.clone().or(Some(icon_name.to_string())),
+                is_device: true,
+                is_multi_sink_app: false,
+                is_input,
+                output_device: None,
+                is_paused: false,
+                grouped_uids: Vec::new(),
+                process_binary: None,
+                media_role: None,
+            });
+        }
+
+        let output_devices = self.devices(false);
+
+        res.extend(streams.into_iter().map(|(&id, node)| {
+            let app_name = stream_app_name(node);
+            let name_count = app_names.iter().filter(|&name| name == &app_name).count();
+
+            AppInfo {
+                uid: id,
+                app_id: node.app_id.clone(),
+                app_name,
+                sink_name: node.media_name.clone(),
+                mute: node.volume.mute,
+                vol_percent: get_volume_percentage(&node.volume.channel_volumes),
+                icon_name: node.icon_name.clone(),
+                is_device: false,
+                is_multi_sink_app: name_count > 1,
+                is_input,
+                output_device: if is_input {
+                    None
+                } else {
+                    self.get_output_device(id, &output_devices)
+                },
+                is_paused: !node.running,
+                grouped_uids: Vec::new(),
+                process_binary: node.process_binary.clone(),
+                media_role: node.media_role.clone(),
+            }
+        }));
+
+        res
+    }
+
+    fn get_node(&self, id: u32) -> Result<&AudioNode, Box<dyn Error>> {
+        self.nodes
+            .get(&id)
+            .ok_or_else(|| format!("No PipeWire node with id {}", id).into())
+    }
+}
+
+impl AudioSystem for PipeWireAudioSystem {
+    fn list_applications(&mut self) -> Result<Vec<AppInfo>, Box<dyn Error>> {
+        self.sync_nodes()?;
+        let graph = self.graph.borrow();
+
+        // The default sink (main PC audio) is only shown if the global flag is set
+        let mut res = graph.list_channels(false, crate::utils::should_show_system_mixer());
+
+        // Microphones and recording apps are opt-in
+        if crate::utils::should_show_input_channels() {
+            res.extend(graph.list_channels(true, true));
+        }
+
+        Ok(res)
+    }
+
+    fn increase_volume(
+        &mut self,
+        app_index: u32,
+        percent: f64,
+        _is_device: bool,
+        _is_input: bool,
+    ) -> Result<(), Box<dyn Error>> {
+        self.change_volume(app_index, percent as f32)
+    }
+
+    fn decrease_volume(
+        &mut self,
+        app_index: u32,
+        percent: f64,
+        _is_device: bool,
+        _is_input: bool,
+    ) -> Result<(), Box<dyn Error>> {
+        self.change_volume(app_index, -(percent as f32))
+    }
+
+    fn mute_volume(
+        &mut self,
+        app_index: u32,
+        mute: bool,
+        _is_device: bool,
+        _is_input: bool,
+    ) -> Result<(), Box<dyn Error>> {
+        self.sync_nodes()?;
+        self.set_props(
+            app_index,
+            Property::new(pw::spa::sys::SPA_PROP_mute, Value::Bool(mute)),
+        )
+    }
+
+    fn set_volume(
+        &mut self,
+        app_index: u32,
+        percent: f32,
+        _is_device: bool,
+        _is_input: bool,
+    ) -> Result<(), Box<dyn Error>> {
+        self.sync_nodes()?;
+        let channel_volumes = scale_channel_volumes(
+            &self
+                .graph
+                .borrow()
+                .get_node(app_index)?
+                .volume
+                .channel_volumes,
+            percent,
+        );
+        self.set_channel_volumes(app_index, channel_volumes)
+    }
+
+    fn get_volume(
+        &mut self,
+        app_index: u32,
+        _is_device: bool,
+        _is_input: bool,
+    ) -> Result<f32, Box<dyn Error>> {
+        self.sync_nodes()?;
+        let graph = self.graph.borrow();
+        Ok(get_volume_percentage(
+            &graph.get_node(app_index)?.volume.channel_volumes,
+        ))
+    }
+
+    fn list_output_devices(&mut self) -> Result<Vec<AudioDevice>, Box<dyn Error>> {
+        self.sync_nodes()?;
+        Ok(self.graph.borrow().devices(false))
+    }
+
+    fn list_input_devices(&mut self) -> Result<Vec<AudioDevice>, Box<dyn Error>> {
+        self.sync_nodes()?;
+        Ok(self.graph.borrow().devices(true))
+    }
+
+    fn set_default_device(
+        &mut self,
+        device_index: u32,
+        is_input: bool,
+        move_streams: bool,
+    ) -> Result<(), Box<dyn Error>> {
+        self.sync_nodes()?;
+        {
+            let graph = self.graph.borrow();
+            let device = graph.get_node(device_index)?;
+            let name = device
+                .name
+                .clone()
+                .ok_or_else(|| format!("PipeWire node {} has no name", device_index))?;
+            let serial = device.serial.clone();
+
+            let (key, stream_class) = if is_input {
+                (CONFIGURED_DEFAULT_SOURCE_KEY, NodeClass::Recorder)
+            } else {
+                (CONFIGURED_DEFAULT_SINK_KEY, NodeClass::Stream)
+            };
+
+            let metadata = graph.default_metadata()?;
+            let value = serde_json::json!({ "name": name }).to_string();
+            metadata.set_property(0, key, Some("Spa:String:JSON"), Some(&value));
+
+            if move_streams && let Some(serial) = serial {
+                for (&id, _) in graph
+                    .nodes
+                    .iter()
+                    .filter(|(_, node)| node.class == stream_class)
+                {
+                    metadata.set_property(id, TARGET_OBJECT_KEY, Some("Spa:Id"), Some(&serial));
+                }
+            }
+        }
+
+        self.roundtrip()
+    }
+
+    fn move_to_sink(&mut self, app_index: u32, sink_index: u32) -> Result<(), Box<dyn Error>> {
+        self.sync_nodes()?;
+        {
+            let graph = self.graph.borrow();
+            graph.get_node(app_index)?;
+            let serial = graph
+                .get_node(sink_index)?
+                .serial
+                .clone()
+                .ok_or_else(|| format!("PipeWire node {} has no serial", sink_index))?;
+            let metadata = graph.default_metadata()?;
+
+            // Same request pipewire-pulse makes when pavucontrol moves a stream
+            metadata.set_property(app_index, TARGET_OBJECT_KEY, Some("Spa:Id"), Some(&serial));
+        }
+        self.roundtrip()
+    }
+}
+
+/// Metadata values for default nodes look like `{ "name": "alsa_output.pci-0000_00_1f.3" }`
+pub(super) fn parse_node_name(value: &str) -> Option<String> {
+    serde_json::from_str::<serde_json::Value>(value)
+        .ok()?
+        .get("name")?
+        .as_str()
+        .map(str::to_string)
+}
+
+fn parse_props(pod: &Pod) -> Option<NodeVolume> {
+    let (_, value) = PodDeserializer::deserialize_any_from(pod.as_bytes()).ok()?;
+    let Value::Object(object) = value else {
+        return None;
+    };
+
+    let mut volume = NodeVolume::default();
+    let mut has_volumes = false;
+    for property in object.properties {
+        match (property.key, property.value) {
+            (pw::spa::sys::SPA_PROP_channelVolumes, Value::ValueArray(ValueArray::Float(v))) => {
+                volume.channel_volumes = v;
+                has_volumes = true;
+            }
+            (pw::spa::sys::SPA_PROP_mute, Value::Bool(mute)) => volume.mute = mute,
+            _ => {}
+        }
+    }
+
+    // Nodes can report several Props objects, only the one carrying volumes is useful
+    has_volumes.then_some(volume)
+}
+
+fn stream_app_name(node: &AudioNode) -> String {
+    node.app_name
+        .clone()
+        .unwrap_or("app_stream".to_string())
+        .to_lowercase()
+}
+
+/// PipeWire stores linear channel volumes, mixers show them on a cubic scale
+fn get_volume_percentage(channel_volumes: &[f32]) -> f32 {
+    if channel_volumes.is_empty() {
+        return 0.0;
+    }
+
+    let average = channel_volumes.iter().sum::<f32>() / channel_volumes.len() as f32;
+    average.cbrt() * 100.0
+}
+
+/// Steps every channel by `delta` on the cubic scale so the balance is kept
+fn step_channel_volumes(channel_volumes: &[f32], delta: f32) -> Vec<f32> {
+    channel_volumes
+        .iter()
+        .map(|&volume| {
+            (volume.cbrt() + delta)
+                .clamp(0.0, MAX_BOOST_VOLUME_PERCENT / 100.0)
+                .powi(3)
+        })
+        .collect()
+}
+
+/// Scales the channels so `get_volume_percentage` reads back `percent`, keeping the balance
+fn scale_channel_volumes(channel_volumes: &[f32], percent: f32) -> Vec<f32> {
+    let max = (MAX_BOOST_VOLUME_PERCENT / 100.0).powi(3);
+    let target = (percent.clamp(0.0, MAX_BOOST_VOLUME_PERCENT) / 100.0).powi(3);
+    let average = if channel_volumes.is_empty() {
+        0.0
+    } else {
+        channel_volumes.iter().sum::<f32>() / channel_volumes.len() as f32
+    };
+
+    channel_volumes
+        .iter()
+        .map(|&volume| {
+            if average > 0.0 {
+                (volume / average * target).min(max)
+            } else {
+                target
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_volumes(actual: &[f32], expected: &[f32]) {
+        assert_eq!(
+            actual.len(),
+            expected.len(),
+            "{:?} vs {:?}",
+            actual,
+            expected
+        );
+        for (a, e) in actual.iter().zip(expected) {
+            assert!((a - e).abs() < 1e-4, "{:?} vs {:?}", actual, expected);
+        }
+    }
+
+    #[test]
+    fn node_name_is_read_from_metadata_json() {
+        assert_eq!(
+            parse_node_name(r#"{ "name": "alsa_output.pci-0000_00_1f.3" }"#).as_deref(),
+            Some("alsa_output.pci-0000_00_1f.3")
+        );
+        assert_eq!(parse_node_name(r#"{ "name": 3 }"#), None);
+        assert_eq!(parse_node_name(r#"{ "id": "sink" }"#), None);
+        assert_eq!(parse_node_name("alsa_output.pci-0000_00_1f.3"), None);
+    }
+
+    #[test]
+    fn volume_percentage_is_the_cube_root_of_the_average() {
+        assert_eq!(get_volume_percentage(&[]), 0.0);
+        assert!((get_volume_percentage(&[1.0, 1.0]) - 100.0).abs() < 1e-3);
+        assert!((get_volume_percentage(&[0.125]) - 50.0).abs() < 1e-3);
+        assert!((get_volume_percentage(&[0.0, 0.25]) - 50.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn volume_steps_on_the_cubic_scale() {
+        assert_volumes(&step_channel_volumes(&[0.125], 0.1), &[0.216]);
+        assert_volumes(&step_channel_volumes(&[0.125, 1.0], -0.5), &[0.0, 0.125]);
+        // Boosting stops at the maximum
+        let max = (MAX_BOOST_VOLUME_PERCENT / 100.0).powi(3);
+        assert_volumes(&step_channel_volumes(&[1.0], 5.0), &[max]);
+    }
+
+    #[test]
+    fn set_volume_keeps_the_channel_balance() {
+        let (high, low) = (0.125 / 0.75, 0.0625 / 0.75);
+        assert_volumes(&scale_channel_volumes(&[1.0, 0.5], 50.0), &[high, low]);
+        // Silent channels all go to the target
+        assert_volumes(&scale_channel_volumes(&[0.0, 0.0], 50.0), &[0.125, 0.125]);
+        let max = (MAX_BOOST_VOLUME_PERCENT / 100.0).powi(3);
+        assert_volumes(&scale_channel_volumes(&[1.0], 1000.0), &[max]);
+        assert_volumes(&scale_channel_volumes(&[1.0], -10.0), &[0.0]);
+    }
+
+    #[test]
+    fn set_volume_reads_back_the_same_percent() {
+        for percent in [0.0, 37.0, 75.0, 100.0, 120.0] {
+            let volumes = scale_channel_volumes(&[1.0, 0.125], percent);
+            assert!(
+                (get_volume_percentage(&volumes) - percent).abs() < 1e-3,
+                "{} read back as {}",
+                percent,
+                get_volume_percentage(&volumes)
+            );
+        }
+    }
+}
@@ -0,0 +1,210 @@
+use super::pipewire::{DEFAULT_METADATA_NAME, DEFAULT_SINK_KEY, DEFAULT_SOURCE_KEY, NodeClass};
+use crate::audio::monitor::{
+    EventFacility, EventKind, connection_established, request_refresh, send_event, supervise,
+};
+use crate::utils;
+use ::pipewire as pw;
+use pw::{
+    context::Context,
+    main_loop::MainLoop,
+    metadata::{Metadata, MetadataListener},
+    node::{Node, NodeListener, NodeState},
+    registry::{GlobalObject, Registry},
+    spa::{param::ParamType, utils::dict::DictRef},
+    types::ObjectType,
+};
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    error::Error,
+    rc::Rc,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+static MONITOR_STARTED: AtomicBool = AtomicBool::new(false);
+
+/// Proxies kept alive so their listeners keep reporting changes
+enum WatchedObject {
+    Node {
+        _node: Node,
+        _listener: NodeListener,
+    },
+    Metadata {
+        _metadata: Metadata,
+        _listener: MetadataListener,
+    },
+    /// Links are not bound, they are only tracked to notice streams moving between sinks
+    Link,
+}
+
+pub fn start_pipewire_monitoring() {
+    if MONITOR_STARTED.load(Ordering::Acquire) {
+        return; // Already started
+    }
+
+    MONITOR_STARTED.store(true, Ordering::Release);
+
+    // PipeWire objects are not Send, so the whole connection lives on its own thread,
+    // reconnecting whenever the daemon goes away
+    std::thread::spawn(move || {
+        println!("Starting PipeWire monitoring...");
+        supervise("PipeWire", run_monitor);
+    });
+}
+
+fn run_monitor() -> Result<(), Box<dyn Error>> {
+    let mainloop = MainLoop::new(None)?;
+    let context = Context::new(&mainloop)?;
+    let core = context.connect(None)?;
+    let registry = Rc::new(core.get_registry()?);
+    let watched: Rc<RefCell<HashMap<u32, WatchedObject>>> = Rc::default();
+
+    let _core_listener = core
+        .add_listener_local()
+        .error({
+            let mainloop = mainloop.clone();
+            move |id, _seq, _res, message| {
+                if id == pw::core::PW_ID_CORE {
+                    eprintln!("PipeWire connection error: {}", message);
+                    mainloop.quit();
+                }
+            }
+        })
+        .register();
+
+    let _registry_listener = registry
+        .add_listener_local()
+        .global({
+            let registry = Rc::downgrade(&registry);
+            let watched = watched.clone();
+            move |global| {
+                let Some(registry) = registry.upgrade() else {
+                    return;
+                };
+
+                match watch_object(&registry, global) {
+                    Ok(Some(object)) => {
+                        watched.borrow_mut().insert(global.id, object);
+                    }
+                    Ok(None) => {}
+                    Err(e) => eprintln!("Failed to watch PipeWire object {}: {}", global.id, e),
+                }
+            }
+        })
+        .global_remove({
+            let watched = watched.clone();
+            move |id| match watched.borrow_mut().remove(&id) {
+                Some(WatchedObject::Node { .. }) => {
+                    println!("Audio application removed");
+                    request_refresh();
+                }
+                Some(WatchedObject::Link) => {
+                    println!("Audio application output changed");
+                    request_refresh();
+                }
+                _ => {}
+            }
+        })
+        .register();
+
+    println!("PipeWire monitoring started successfully");
+    connection_established();
+
+    // Only returns once the connection is gone
+    mainloop.run();
+
+    Err("PipeWire connection lost".into())
+}
+
+fn watch_object(
+    registry: &Registry,
+    global: &GlobalObject<&DictRef>,
+) -> Result<Option<WatchedObject>, Box<dyn Error>> {
+    let prop = |key: &str| global.props.and_then(|props| props.get(key));
+
+    match global.type_ {
+        ObjectType::Node => {
+            let Some(class) = NodeClass::from_media_class(prop("media.class")) else {
+                return Ok(None);
+            };
+
+            let node: Node = registry.bind(global)?;
+            let id = global.id;
+            let was_running = Cell::new(None);
+            let listener = node
+                .add_listener_local()
+                // Apps pausing or resuming playback can hide or show their stream
+                .info(move |info| {
+                    if class != NodeClass::Stream {
+                        return;
+                    }
+                    let running = matches!(info.state(), NodeState::Running);
+                    if was_running
+                        .replace(Some(running))
+                        .is_some_and(|was| was != running)
+                    {
+                        println!("Audio application paused/resumed");
+                        request_refresh();
+                    }
+                })
+                .param(move |_seq, _id, _index, _next, _param| match class {
+                    NodeClass::Stream => {
+                        send_event(EventFacility::PlaybackStream, EventKind::Changed, id);
+                    }
+                    NodeClass::Sink => {
+                        send_event(EventFacility::OutputDevice, EventKind::Changed, id);
+                    }
+                    // Recording apps and microphones only matter when input channels are shown
+                    NodeClass::Recorder | NodeClass::Source => {
+                        if utils::should_show_input_channels() {
+                            let facility = if class == NodeClass::Source {
+                                EventFacility::InputDevice
+                            } else {
+                                EventFacility::RecordingStream
+                            };
+                            send_event(facility, EventKind::Changed, id);
+                        }
+                    }
+                })
+                .register();
+            node.subscribe_params(&[ParamType::Props]);
+
+            if class == NodeClass::Stream {
+                println!("New audio application detected");
+                send_event(EventFacility::PlaybackStream, EventKind::New, id);
+            } else if class == NodeClass::Recorder && utils::should_show_input_channels() {
+                println!("New audio application detected");
+                send_event(EventFacility::RecordingStream, EventKind::New, id);
+            }
+
+            Ok(Some(WatchedObject::Node {
+                _node: node,
+                _listener: listener,
+            }))
+        }
+        ObjectType::Metadata if prop("metadata.name") == Some(DEFAULT_METADATA_NAME) => {
+            let metadata: Metadata = registry.bind(global)?;
+            let listener = metadata
+                .add_listener_local()
+                .property(|_subject, key, _type, _value| {
+                    if key == Some(DEFAULT_SINK_KEY) || key == Some(DEFAULT_SOURCE_KEY) {
+                        println!("Default device changed");
+                        request_refresh();
+                    }
+                    0
+                })
+                .register();
+
+            Ok(Some(WatchedObject::Metadata {
+                _metadata: metadata,
+                _listener: listener,
+            }))
+        }
+        ObjectType::Link => {
+            println!("Audio application output changed");
+            request_refresh();
+            Ok(Some(WatchedObject::Link))
+        }
+        _ => Ok(None),
+    }
+}
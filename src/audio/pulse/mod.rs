@@ -1,5 +1,8 @@
+#[allow(clippy::module_inception)]
 pub mod pulse;
+pub mod pulse_meter;
 pub mod pulse_monitor;
 
 pub use pulse::PulseAudioSystem;
+pub use pulse_meter::start_pulse_meters;
 pub use pulse_monitor::start_pulse_monitoring;
@@ -1,20 +1,68 @@
-use crate::audio::{AppInfo, AudioSystem};
-use libpulse_binding::volume::ChannelVolumes;
-use pulsectl::controllers::{AppControl, DeviceControl, SinkController};
+use crate::audio::{
+    AppInfo, AudioDevice, AudioSystem, MAX_BOOST_VOLUME_PERCENT, identity::resolve_app_id,
+};
+use libpulse_binding::volume::{ChannelVolumes, Volume};
+use pulsectl::controllers::types::{ApplicationInfo, DeviceInfo};
+use pulsectl::controllers::{AppControl, DeviceControl, SinkController, SourceController};
 use std::error::Error;
 
-const PA_VOLUME_NORM: u32 = 98304; // 150% in PulseAudio
+const PA_VOLUME_NORM: u32 = Volume::NORMAL.0; // 100% in PulseAudio, higher values amplify
+
+// media.name of the streams pavucontrol opens to draw its level meters
+pub(super) const PEAK_DETECT_MEDIA_NAME: &str = "Peak detect";
 
 pub struct PulseAudioSystem {
     controller: SinkController,
+    // Only connected once input channels are used
+    source_controller: Option<SourceController>,
 }
 
 impl PulseAudioSystem {
     pub fn new() -> Result<Self, Box<dyn Error>> {
         Ok(Self {
             controller: SinkController::create()?,
+            source_controller: None,
         })
     }
+
+    fn sources(&mut self) -> Result<&mut SourceController, Box<dyn Error>> {
+        if self.source_controller.is_none() {
+            self.source_controller = Some(SourceController::create()?);
+        }
+
+        Ok(self.source_controller.as_mut().unwrap())
+    }
+
+    /// Default source and recording streams (source outputs)
+    fn list_input_applications(&mut self) -> Result<Vec<AppInfo>, Box<dyn Error>> {
+        let mut res: Vec<AppInfo> = Vec::new();
+        let sources = self.sources()?;
+
+        let recorders: Vec<_> = sources
+            .list_applications()?
+            .into_iter()
+            .filter(|app| {
+                app.proplist.get_str("media.name").as_deref() != Some(PEAK_DETECT_MEDIA_NAME)
+            })
+            .collect();
+
+        let mut app_names: Vec<String> = recorders.iter().map(stream_app_name).collect();
+
+        if let Ok(default_source) = sources.get_default_device() {
+            let device = device_app_info(default_source, true);
+            app_names.push(device.app_name.clone());
+            res.push(device);
+        }
+
+        res.extend(recorders.into_iter().map(|app| {
+            let app_name = stream_app_name(&app);
+            let name_count = app_names.iter().filter(|&name| name == &app_name).count();
+
+            stream_app_info(app, true, name_count > 1, None)
+        }));
+
+        Ok(res)
+    }
 }
 
 impl AudioSystem for PulseAudioSystem {
@@ -23,73 +71,94 @@ impl AudioSystem for PulseAudioSystem {
 
         // Add individual applications first to collect all app names
         let apps = self.controller.list_applications()?;
+        let output_devices = if apps.is_empty() {
+            Vec::new()
+        } else {
+            self.list_output_devices().unwrap_or_default()
+        };
 
         // Collect all app names including system mixer if present
-        let mut app_names: Vec<String> = apps
-            .iter()
-            .map(|app| {
-                app.proplist
-                    .get_str("application.name")
-                    .unwrap_or("app_stream".to_string())
-                    .to_lowercase()
-            })
-            .collect();
+        let mut app_names: Vec<String> = apps.iter().map(stream_app_name).collect();
 
-        // Add the default system sink (main PC audio) only if the global flag is set
+        // Add the default system sink (main PC audio) only if the global flag is set.
+        // The default is looked up on every refresh so the channel follows device switches
         if crate::utils::should_show_system_mixer()
             && let Ok(default_sink) = self.controller.get_default_device()
         {
-            let system_name = default_sink
-                .description
-                .clone()
-                .unwrap_or("System Audio".to_string());
+            let device = device_app_info(default_sink, false);
 
             // Add system mixer name to app_names for duplicate detection
-            app_names.push(system_name.clone());
-
-            res.push(AppInfo {
-                uid: default_sink.index,
-                app_name: system_name,
-                sink_name: Some("System Audio".to_string()),
-                mute: default_sink.mute,
-                vol_percent: get_pulse_app_volume_percentage(&default_sink.volume),
-                icon_name: Some("audio-card".to_string()),
-                is_device: true,
-                is_multi_sink_app: false,
-            });
+            app_names.push(device.app_name.clone());
+            res.push(device);
         }
 
         res.extend(apps.into_iter().map(|app| {
-            let app_name = app
-                .proplist
-                .get_str("application.name")
-                .unwrap_or("app_stream".to_string())
-                .to_lowercase();
-
+            let app_name = stream_app_name(&app);
             let name_count = app_names.iter().filter(|&name| name == &app_name).count();
+            let output_device = output_devices
+                .iter()
+                .find(|device| device.index == app.connection_id)
+                .cloned();
 
-            AppInfo {
-                uid: app.index,
-                app_name,
-                sink_name: app.name,
-                mute: app.mute,
-                vol_percent: get_pulse_app_volume_percentage(&app.volume),
-                icon_name: app.proplist.get_str("application.icon_name"),
-                is_device: false,
-                is_multi_sink_app: name_count > 1,
-            }
+            stream_app_info(app, false, name_count > 1, output_device)
         }));
 
+        // Microphones and recording apps are opt-in
+        if crate::utils::should_show_input_channels() {
+            match self.list_input_applications() {
+                Ok(inputs) => res.extend(inputs),
+                Err(e) => eprintln!("Failed to list input channels: {}", e),
+            }
+        }
+
         Ok(res)
     }
 
+    fn get_application(
+        &mut self,
+        app_index: u32,
+        is_device: bool,
+        is_input: bool,
+    ) -> Result<Option<AppInfo>, Box<dyn Error>> {
+        let app = match (is_device, is_input) {
+            (true, true) => device_app_info(self.sources()?.get_device_by_index(app_index)?, true),
+            (true, false) => {
+                device_app_info(self.controller.get_device_by_index(app_index)?, false)
+            }
+            (false, true) => stream_app_info(
+                self.sources()?.get_app_by_index(app_index)?,
+                true,
+                false,
+                None,
+            ),
+            (false, false) => {
+                let app = self.controller.get_app_by_index(app_index)?;
+                let output_device = self
+                    .list_output_devices()?
+                    .into_iter()
+                    .find(|device| device.index == app.connection_id);
+                stream_app_info(app, false, false, output_device)
+            }
+        };
+
+        Ok(Some(app))
+    }
+
     fn increase_volume(
         &mut self,
         app_index: u32,
         percent: f64,
         is_device: bool,
+        is_input: bool,
     ) -> Result<(), Box<dyn Error>> {
-        if is_device {
+        if is_input {
+            let sources = self.sources()?;
+            if is_device {
+                sources.increase_device_volume_by_percent(app_index, percent);
+            } else {
+                sources.increase_app_volume_by_percent(app_index, percent);
+            }
+        } else if is_device {
             self.controller
                 .increase_device_volume_by_percent(app_index, percent);
         } else {
@@ -104,8 +173,16 @@ impl AudioSystem for PulseAudioSystem {
         app_index: u32,
         percent: f64,
         is_device: bool,
+        is_input: bool,
     ) -> Result<(), Box<dyn Error>> {
-        if is_device {
+        if is_input {
+            let sources = self.sources()?;
+            if is_device {
+                sources.decrease_device_volume_by_percent(app_index, percent);
+            } else {
+                sources.decrease_app_volume_by_percent(app_index, percent);
+            }
+        } else if is_device {
             self.controller
                 .decrease_device_volume_by_percent(app_index, percent);
         } else {
@@ -120,14 +197,296 @@ impl AudioSystem for PulseAudioSystem {
         app_index: u32,
         mute: bool,
         is_device: bool,
+        is_input: bool,
     ) -> Result<(), Box<dyn Error>> {
-        if is_device {
+        if is_input {
+            let sources = self.sources()?;
+            if is_device {
+                sources.set_device_mute_by_index(app_index, mute);
+            } else {
+                // SourceController::set_app_mute mutes the source with that index
+                // instead of the source output, so talk to the introspector directly
+                let op = sources
+                    .handler
+                    .introspect
+                    .set_source_output_mute(app_index, mute, None);
+                sources.handler.wait_for_operation(op)?;
+            }
+        } else if is_device {
             self.controller.set_device_mute_by_index(app_index, mute);
         } else {
             self.controller.set_app_mute(app_index, mute)?;
         }
         Ok(())
     }
+
+    fn set_volume(
+        &mut self,
+        app_index: u32,
+        percent: f32,
+        is_device: bool,
+        is_input: bool,
+    ) -> Result<(), Box<dyn Error>> {
+        if is_input {
+            let sources = self.sources()?;
+            if is_device {
+                let mut volumes = sources.get_device_by_index(app_index)?.volume;
+                scale_pulse_volumes(&mut volumes, percent);
+                sources.set_device_volume_by_index(app_index, &volumes);
+            } else {
+                let mut volumes = sources.get_app_by_index(app_index)?.volume;
+                scale_pulse_volumes(&mut volumes, percent);
+                let op = sources
+                    .handler
+                    .introspect
+                    .set_source_output_volume(app_index, &volumes, None);
+                sources.handler.wait_for_operation(op)?;
+            }
+        } else if is_device {
+            let mut volumes = self.controller.get_device_by_index(app_index)?.volume;
+            scale_pulse_volumes(&mut volumes, percent);
+            self.controller
+                .set_device_volume_by_index(app_index, &volumes);
+        } else {
+            let mut volumes = self.controller.get_app_by_index(app_index)?.volume;
+            scale_pulse_volumes(&mut volumes, percent);
+            let op = self
+                .controller
+                .handler
+                .introspect
+                .set_sink_input_volume(app_index, &volumes, None);
+            self.controller.handler.wait_for_operation(op)?;
+        }
+        Ok(())
+    }
+
+    fn get_volume(
+        &mut self,
+        app_index: u32,
+        is_device: bool,
+        is_input: bool,
+    ) -> Result<f32, Box<dyn Error>> {
+        let volumes = if is_input {
+            let sources = self.sources()?;
+            if is_device {
+                sources.get_device_by_index(app_index)?.volume
+            } else {
+                sources.get_app_by_index(app_index)?.volume
+            }
+        } else if is_device {
+            self.controller.get_device_by_index(app_index)?.volume
+        } else {
+            self.controller.get_app_by_index(app_index)?.volume
+        };
+
+        Ok(get_pulse_app_volume_percentage(&volumes))
+    }
+
+    fn list_output_devices(&mut self) -> Result<Vec<AudioDevice>, Box<dyn Error>> {
+        list_devices(&mut self.controller, "Output")
+    }
+
+    fn list_input_devices(&mut self) -> Result<Vec<AudioDevice>, Box<dyn Error>> {
+        let sources = self.sources()?;
+        let monitors: Vec<u32> = sources
+            .list_devices()?
+            .into_iter()
+            .filter(|device| device.monitor.is_some())
+            .map(|device| device.index)
+            .collect();
+
+        Ok(list_devices(sources, "Microphone")?
+            .into_iter()
+            .filter(|device| !monitors.contains(&device.index))
+            .collect())
+    }
+
+    fn set_default_device(
+        &mut self,
+        device_index: u32,
+        is_input: bool,
+        move_streams: bool,
+    ) -> Result<(), Box<dyn Error>> {
+        if is_input {
+            switch_default_device(self.sources()?, device_index, move_streams)
+        } else {
+            switch_default_device(&mut self.controller, device_index, move_streams)
+        }
+    }
+
+    fn move_to_sink(&mut self, app_index: u32, sink_index: u32) -> Result<(), Box<dyn Error>> {
+        if !self.controller.move_app_by_index(app_index, sink_index)? {
+            return Err(
+                format!("Failed to move stream {} to sink {}", app_index, sink_index).into(),
+            );
+        }
+        Ok(())
+    }
+}
+
+fn stream_app_name(app: &ApplicationInfo) -> String {
+    app.proplist
+        .get_str("application.name")
+        .unwrap_or("app_stream".to_string())
+        .to_lowercase()
+}
+
+/// Channel info of a playback (sink input) or recording (source output) stream
+fn stream_app_info(
+    app: ApplicationInfo,
+    is_input: bool,
+    is_multi_sink_app: bool,
+    output_device: Option<AudioDevice>,
+) -> AppInfo {
+    AppInfo {
+        uid: app.index,
+        app_id: resolve_app_id(|key| app.proplist.get_str(key)),
+        app_name: stream_app_name(&app),
+        mute: app.mute,
+        vol_percent: get_pulse_app_volume_percentage(&app.volume),
+        icon_name: app.proplist.get_str("application.icon_name"),
+        sink_name: app.name,
+        is_device: false,
+        is_multi_sink_app,
+        is_input,
+        output_device,
+        is_paused: app.corked,
+        grouped_uids: Vec::new(),
+        process_binary: app.proplist.get_str("application.process.binary"),
+        media_role: app.proplist.get_str("media.role"),
+    }
+}
+
+/// Channel info of the system (sink) or microphone (source) channel
+fn device_app_info(device: DeviceInfo, is_input: bool) -> AppInfo {
+    let (fallback_name, fallback_icon_name) = if is_input {
+        ("Microphone", "audio-input-microphone")
+    } else {
+        ("System Audio", "audio-card")
+    };
+
+    let app_name = device
+        .description
+        .clone()
+        .unwrap_or(fallback_name.to_string());
+
+    AppInfo {
+        uid: device.index,
+        app_id: device.name.clone().unwrap_or(app_name.clone()),
+        app_name,
+        sink_name: Some(fallback_name.to_string()),
+        mute: device.mute,
+        vol_percent: get_pulse_app_volume_percentage(&device.volume),
+        icon_name: device
+            .proplist
+            .get_str("device.icon_name")
+            .or(Some(fallback_icon_name.to_string())),
+        is_device: true,
+        is_multi_sink_app: false,
+        is_input,
+        output_device: None,
+        is_paused: false,
+        grouped_uids: Vec::new(),
+        process_binary: None,
+        media_role: None,
+    }
+}
+
+/// Sinks or sources of a controller, flagging the server's default one
+fn list_devices<C>(
+    controller: &mut C,
+    fallback_name: &str,
+) -> Result<Vec<AudioDevice>, Box<dyn Error>>
+where
+    C: DeviceControl<DeviceInfo>,
+{
+    let default_name = controller
+        .get_default_device()
+        .ok()
+        .and_then(|device| device.name);
+
+    Ok(controller
+        .list_devices()?
+        .into_iter()
+        .map(|device| {
+            let name = device.name.clone().unwrap_or_default();
+            AudioDevice {
+                index: device.index,
+                description: device
+                    .description
+                    .clone()
+                    .or(device.name.clone())
+                    .unwrap_or(fallback_name.to_string()),
+                icon_name: device.proplist.get_str("device.icon_name"),
+                is_default: device.name.is_some() && device.name == default_name,
+                name,
+            }
+        })
+        .collect())
+}
+
+/// Make a sink or source the default one, moving the other streams onto it if asked to
+fn switch_default_device<C>(
+    controller: &mut C,
+    device_index: u32,
+    move_streams: bool,
+) -> Result<(), Box<dyn Error>>
+where
+    C: DeviceControl<DeviceInfo> + AppControl<ApplicationInfo>,
+{
+    let name = controller
+        .get_device_by_index(device_index)?
+        .name
+        .ok_or_else(|| format!("Device {} has no name", device_index))?;
+
+    if !controller.set_default_device(&name)? {
+        return Err(format!("Failed to make {} the default device", name).into());
+    }
+
+    if move_streams {
+        for app in controller.list_applications()? {
+            // Level meters of other mixers stay on the monitors they watch
+            if app.connection_id == device_index
+                || app.proplist.get_str("media.name").as_deref() == Some(PEAK_DETECT_MEDIA_NAME)
+            {
+                continue;
+            }
+
+            if let Err(e) = controller.move_app_by_index(app.index, device_index) {
+                eprintln!("Failed to move stream {} to {}: {}", app.index, name, e);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Channel volume for a percentage, capped at the boost limit
+fn get_pulse_volume(percent: f32) -> Volume {
+    let percent = percent.clamp(0.0, MAX_BOOST_VOLUME_PERCENT);
+    Volume((percent / 100.0 * PA_VOLUME_NORM as f32) as u32)
+}
+
+/// Scales the channels so their average, which is what is read back, is `percent`.
+/// The balance between the channels is kept.
+fn scale_pulse_volumes(channel_volumes: &mut ChannelVolumes, percent: f32) {
+    let target = get_pulse_volume(percent).0;
+    let max = get_pulse_volume(MAX_BOOST_VOLUME_PERCENT).0;
+
+    let channels = channel_volumes.get_mut();
+    if channels.is_empty() {
+        return;
+    }
+    let average =
+        channels.iter().map(|volume| volume.0 as f64).sum::<f64>() / channels.len() as f64;
+
+    for volume in channels.iter_mut() {
+        volume.0 = if average > 0.0 {
+            ((volume.0 as f64 * target as f64 / average).round() as u32).min(max)
+        } else {
+            target
+        };
+    }
 }
 
 fn get_pulse_app_volume_percentage(channel_volumes: &ChannelVolumes) -> f32 {
@@ -142,7 +501,80 @@ fn get_pulse_app_volume_percentage(channel_volumes: &ChannelVolumes) -> f32 {
         .sum();
 
     let avg_volume = total_volume as f32 / channel_count as f32;
-    let perc = (avg_volume / PA_VOLUME_NORM as f32) * 100.0;
+    (avg_volume / PA_VOLUME_NORM as f32) * 100.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn channel_volumes(percents: &[f32]) -> ChannelVolumes {
+        let mut volumes = ChannelVolumes::default();
+        volumes.set_len(percents.len() as u8);
+        for (volume, &percent) in volumes.get_mut().iter_mut().zip(percents) {
+            *volume = get_pulse_volume(percent);
+        }
+        volumes
+    }
+
+    #[test]
+    fn percentages_map_to_the_pulse_volume_scale() {
+        assert_eq!(get_pulse_volume(100.0), Volume::NORMAL);
+        assert_eq!(get_pulse_volume(50.0), Volume(PA_VOLUME_NORM / 2));
+        assert_eq!(get_pulse_volume(-20.0), Volume::MUTED);
+        assert_eq!(
+            get_pulse_app_volume_percentage(&channel_volumes(&[100.0, 50.0])),
+            75.0
+        );
+        assert_eq!(
+            get_pulse_app_volume_percentage(&ChannelVolumes::default()),
+            0.0
+        );
+    }
 
-    perc.min(100.0)
+    #[test]
+    fn boost_is_capped() {
+        let max = get_pulse_volume(MAX_BOOST_VOLUME_PERCENT);
+        assert_eq!(max, Volume(PA_VOLUME_NORM * 3 / 2));
+        assert_eq!(get_pulse_volume(1000.0), max);
+        assert_eq!(
+            get_pulse_app_volume_percentage(&channel_volumes(&[150.0])),
+            150.0
+        );
+    }
+
+    #[test]
+    fn set_volume_reads_back_the_same_percent() {
+        for percent in [0.0, 37.0, 56.0, 75.0, 100.0] {
+            let mut volumes = channel_volumes(&[100.0, 50.0]);
+            scale_pulse_volumes(&mut volumes, percent);
+            let read = get_pulse_app_volume_percentage(&volumes);
+            assert!(
+                (read - percent).abs() < 0.01,
+                "{} read back as {}",
+                percent,
+                read
+            );
+        }
+    }
+
+    #[test]
+    fn set_volume_keeps_the_channel_balance() {
+        let mut volumes = channel_volumes(&[100.0, 50.0]);
+        scale_pulse_volumes(&mut volumes, 75.0);
+        let [left, right] = volumes.get() else {
+            panic!("expected two channels");
+        };
+        assert!((left.0 as f32 / right.0 as f32 - 2.0).abs() < 1e-3);
+
+        // Silent channels all go to the target
+        let mut volumes = channel_volumes(&[0.0, 0.0]);
+        scale_pulse_volumes(&mut volumes, 50.0);
+        assert_eq!(volumes.get(), [get_pulse_volume(50.0); 2]);
+
+        // No channel is boosted past the limit
+        let mut volumes = channel_volumes(&[100.0, 50.0]);
+        scale_pulse_volumes(&mut volumes, 140.0);
+        assert_eq!(volumes.get()[0], get_pulse_volume(MAX_BOOST_VOLUME_PERCENT));
+    }
 }
@@ -0,0 +1,177 @@
+use super::pulse::PEAK_DETECT_MEDIA_NAME;
+use crate::audio::meter::{report_peak, watched_streams};
+use libpulse_binding::{
+    context::{Context, FlagSet, State},
+    def::BufferAttr,
+    mainloop::standard::{IterateResult, Mainloop},
+    sample::{Format, Spec},
+    stream::{self, PeekResult, Stream},
+};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::error::Error;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+// Peaks per second the server sends for every stream, like pavucontrol
+const PEAK_RATE: u32 = 25;
+// How often the sampled streams are matched against the ones shown on the deck
+const SYNC_INTERVAL: Duration = Duration::from_millis(500);
+// Pause between mainloop iterations
+const ITERATE_INTERVAL: Duration = Duration::from_millis(10);
+// Wait before trying again after losing the server
+const RECONNECT_DELAY: Duration = Duration::from_secs(2);
+
+/// A peak detecting record stream on the monitor of the sink a stream plays to
+struct PeakStream {
+    stream: Rc<RefCell<Stream>>,
+    device_name: String,
+}
+
+impl Drop for PeakStream {
+    fn drop(&mut self) {
+        let mut stream = self.stream.borrow_mut();
+        stream.set_read_callback(None);
+        let _ = stream.disconnect();
+    }
+}
+
+/// Samples the peaks of the playback streams shown on the deck on a thread of its own
+pub fn start_pulse_meters() {
+    std::thread::spawn(move || {
+        println!("Starting PulseAudio level meters...");
+
+        loop {
+            if let Err(e) = run_meters() {
+                eprintln!("PulseAudio level meters stopped: {}", e);
+            }
+            std::thread::sleep(RECONNECT_DELAY);
+        }
+    });
+}
+
+fn run_meters() -> Result<(), Box<dyn Error>> {
+    let mut mainloop = Mainloop::new().ok_or("Failed to create PulseAudio mainloop")?;
+    let mut context = Context::new(&mainloop, "VolumeControllerMeters")
+        .ok_or("Failed to create PulseAudio context")?;
+
+    context.connect(None, FlagSet::NOFLAGS, None)?;
+
+    let mut streams: HashMap<u32, PeakStream> = HashMap::new();
+    let mut last_sync = Instant::now() - SYNC_INTERVAL;
+
+    loop {
+        if let IterateResult::Quit(_) | IterateResult::Err(_) = mainloop.iterate(false) {
+            return Err("PulseAudio mainloop stopped".into());
+        }
+
+        match context.get_state() {
+            State::Ready => {}
+            State::Failed | State::Terminated => return Err("PulseAudio connection lost".into()),
+            _ => {
+                std::thread::sleep(ITERATE_INTERVAL);
+                continue;
+            }
+        }
+
+        if last_sync.elapsed() >= SYNC_INTERVAL {
+            sync_streams(&mut context, &mut streams);
+            last_sync = Instant::now();
+        }
+
+        std::thread::sleep(ITERATE_INTERVAL);
+    }
+}
+
+/// Start sampling newly shown streams and stop sampling the ones that went away
+/// or moved to another output device
+fn sync_streams(context: &mut Context, streams: &mut HashMap<u32, PeakStream>) {
+    let watched = watched_streams();
+
+    streams.retain(|uid, peak_stream| {
+        watched.get(uid) == Some(&peak_stream.device_name)
+            && peak_stream.stream.borrow().get_state().is_good()
+    });
+
+    for (uid, device_name) in watched {
+        if streams.contains_key(&uid) {
+            continue;
+        }
+
+        match create_peak_stream(context, uid, &device_name) {
+            Ok(stream) => {
+                streams.insert(
+                    uid,
+                    PeakStream {
+                        stream,
+                        device_name,
+                    },
+                );
+            }
+            Err(e) => eprintln!("Failed to sample the level of stream {}: {}", uid, e),
+        }
+    }
+}
+
+fn create_peak_stream(
+    context: &mut Context,
+    uid: u32,
+    device_name: &str,
+) -> Result<Rc<RefCell<Stream>>, Box<dyn Error>> {
+    let spec = Spec {
+        format: Format::FLOAT32NE,
+        channels: 1,
+        rate: PEAK_RATE,
+    };
+
+    // Streams named like pavucontrol's meters are left out of the recording apps
+    let stream = Stream::new(context, PEAK_DETECT_MEDIA_NAME, &spec, None)
+        .ok_or("Failed to create peak detect stream")?;
+    let stream = Rc::new(RefCell::new(stream));
+
+    stream.borrow_mut().set_monitor_stream(uid)?;
+
+    let weak_stream = Rc::downgrade(&stream);
+    stream
+        .borrow_mut()
+        .set_read_callback(Some(Box::new(move |_length| {
+            let Some(stream) = weak_stream.upgrade() else {
+                return;
+            };
+            let mut stream = stream.borrow_mut();
+
+            // With peak detection every sample is the peak of one period
+            let peak = match stream.peek() {
+                Ok(PeekResult::Data(data)) => data
+                    .chunks_exact(size_of::<f32>())
+                    .filter_map(|sample| sample.try_into().ok())
+                    .map(|sample| f32::from_ne_bytes(sample).abs())
+                    .reduce(f32::max),
+                Ok(PeekResult::Hole(_)) => None,
+                Ok(PeekResult::Empty) | Err(_) => return,
+            };
+            let _ = stream.discard();
+
+            if let Some(peak) = peak {
+                report_peak(uid, peak);
+            }
+        })));
+
+    let attr = BufferAttr {
+        maxlength: u32::MAX,
+        tlength: 0,
+        prebuf: 0,
+        minreq: 0,
+        fragsize: size_of::<f32>() as u32,
+    };
+    stream.borrow_mut().connect_record(
+        Some(&format!("{}.monitor", device_name)),
+        Some(&attr),
+        stream::FlagSet::PEAK_DETECT
+            | stream::FlagSet::ADJUST_LATENCY
+            | stream::FlagSet::DONT_MOVE
+            | stream::FlagSet::DONT_INHIBIT_AUTO_SUSPEND,
+    )?;
+
+    Ok(stream)
+}
@@ -1,26 +1,23 @@
-use crate::{audio, mixer, utils};
+use crate::audio::monitor::{
+    EventFacility, EventKind, connection_established, request_refresh, send_event, supervise,
+};
+use crate::utils;
 use libpulse_binding::{
     context::{
-        Context, FlagSet,
+        Context, FlagSet, State,
         subscribe::{Facility, InterestMaskSet, Operation},
     },
     mainloop::threaded::Mainloop,
     proplist::Proplist,
 };
-use std::sync::LazyLock;
+use std::error::Error;
 use std::sync::atomic::{AtomicBool, Ordering};
-use tokio::sync::mpsc;
+use std::time::Duration;
 
-static MONITOR_STARTED: AtomicBool = AtomicBool::new(false);
+// How often the monitor checks that the server is still there
+const CONNECTION_CHECK_INTERVAL: Duration = Duration::from_millis(500);
 
-// Global channel for refresh requests
-static REFRESH_CHANNEL: LazyLock<(
-    mpsc::UnboundedSender<()>,
-    std::sync::Mutex<Option<mpsc::UnboundedReceiver<()>>>,
-)> = LazyLock::new(|| {
-    let (tx, rx) = mpsc::unbounded_channel();
-    (tx, std::sync::Mutex::new(Some(rx)))
-});
+static MONITOR_STARTED: AtomicBool = AtomicBool::new(false);
 
 pub fn start_pulse_monitoring() {
     if MONITOR_STARTED.load(Ordering::Acquire) {
@@ -29,155 +26,123 @@ pub fn start_pulse_monitoring() {
 
     MONITOR_STARTED.store(true, Ordering::Release);
 
-    // Start the refresh processor in tokio runtime
-    start_refresh_processor();
-
-    // Start PulseAudio monitoring in a regular thread
+    // Start PulseAudio monitoring in a regular thread, reconnecting whenever the server goes away
     std::thread::spawn(move || {
         println!("Starting PulseAudio monitoring...");
+        supervise("PulseAudio", monitor_session);
+    });
+}
 
-        // Create mainloop
-        let mut mainloop = match Mainloop::new() {
-            Some(m) => m,
-            None => {
-                eprintln!("Failed to create PulseAudio mainloop");
-                return;
-            }
-        };
-
-        if mainloop.start().is_err() {
-            eprintln!("Failed to start PulseAudio mainloop");
-            return;
-        }
+/// Connects to PulseAudio, subscribes to its events and blocks until the connection is lost
+fn monitor_session() -> Result<(), Box<dyn Error>> {
+    // Create mainloop
+    let mut mainloop = Mainloop::new().ok_or("Failed to create PulseAudio mainloop")?;
 
-        // Create context
-        let mut proplist = Proplist::new().unwrap();
-        proplist
-            .set_str("application.name", "Volume Controller")
-            .unwrap();
-
-        let mut context =
-            match Context::new_with_proplist(&mainloop, "VolumeControllerMonitor", &proplist) {
-                Some(c) => c,
-                None => {
-                    eprintln!("Failed to create PulseAudio context");
-                    return;
-                }
-            };
+    if mainloop.start().is_err() {
+        return Err("Failed to start PulseAudio mainloop".into());
+    }
 
-        // Get the sender for refresh requests
-        let refresh_sender = REFRESH_CHANNEL.0.clone();
+    // Create context
+    let mut proplist = Proplist::new().ok_or("Failed to create PulseAudio proplist")?;
+    proplist
+        .set_str("application.name", "Volume Controller")
+        .map_err(|_| "Failed to set PulseAudio application name")?;
 
-        // Set up subscription callback
-        context.set_subscribe_callback(Some(Box::new(move |facility, operation, _index| {
-            match (facility, operation) {
-                (Some(Facility::SinkInput), Some(Operation::New)) => {
-                    println!("New audio application detected");
-                    let _ = refresh_sender.send(());
-                }
-                (Some(Facility::SinkInput), Some(Operation::Removed)) => {
-                    println!("Audio application removed");
-                    let _ = refresh_sender.send(());
-                }
-                (Some(Facility::SinkInput), Some(Operation::Changed)) => {
-                    println!("Audio application volume/mute changed");
-                    let _ = refresh_sender.send(());
-                }
-                (Some(Facility::Sink), Some(Operation::Changed)) => {
-                    println!("System sink (main PC audio) volume/mute changed");
-                    let _ = refresh_sender.send(());
-                }
-                _ => {}
-            }
-        })));
+    let mut context = Context::new_with_proplist(&mainloop, "VolumeControllerMonitor", &proplist)
+        .ok_or("Failed to create PulseAudio context")?;
 
-        // Connect to PulseAudio
-        if context.connect(None, FlagSet::NOFLAGS, None).is_err() {
-            eprintln!("Failed to connect to PulseAudio");
+    // Set up subscription callback
+    context.set_subscribe_callback(Some(Box::new(move |facility, operation, index| {
+        let (Some(facility), Some(operation)) = (facility, operation) else {
             return;
-        }
+        };
+        let kind = match operation {
+            Operation::New => EventKind::New,
+            Operation::Changed => EventKind::Changed,
+            Operation::Removed => EventKind::Removed,
+        };
 
-        // Wait for connection
-        loop {
-            match context.get_state() {
-                libpulse_binding::context::State::Ready => break,
-                libpulse_binding::context::State::Failed => {
-                    eprintln!("PulseAudio connection failed");
-                    return;
+        match facility {
+            Facility::SinkInput => {
+                match kind {
+                    EventKind::New => println!("New audio application detected"),
+                    EventKind::Removed => println!("Audio application removed"),
+                    EventKind::Changed => {}
                 }
-                libpulse_binding::context::State::Terminated => {
-                    eprintln!("PulseAudio connection terminated");
-                    return;
-                }
-                _ => {
-                    std::thread::sleep(std::time::Duration::from_millis(50));
+                send_event(EventFacility::PlaybackStream, kind, index);
+            }
+            // Sinks coming and going also changes the output device choices
+            Facility::Sink => {
+                if kind != EventKind::Changed {
+                    println!("Output device added or removed");
                 }
+                send_event(EventFacility::OutputDevice, kind, index);
+            }
+            // Recording apps and microphones only matter when input channels are shown
+            Facility::SourceOutput if utils::should_show_input_channels() => {
+                send_event(EventFacility::RecordingStream, kind, index);
+            }
+            Facility::Source if utils::should_show_input_channels() => {
+                send_event(EventFacility::InputDevice, kind, index);
+            }
+            // Default sink or source switched, e.g. from the desktop's sound settings
+            Facility::Server => {
+                println!("Default audio device changed");
+                request_refresh();
             }
+            // Profile switches (e.g. headset plugged in) replace the card's sinks
+            Facility::Card => {
+                println!("Sound card changed");
+                request_refresh();
+            }
+            _ => {}
         }
+    })));
 
-        // Subscribe to sink input events and sink events
-        context.subscribe(
-            InterestMaskSet::SINK_INPUT | InterestMaskSet::SINK,
-            |_success| {},
-        );
-
-        println!("PulseAudio monitoring started successfully");
+    // Connect to PulseAudio
+    let result = watch_connection(&mut context);
 
-        // Keep the context and mainloop alive
-        loop {
-            std::thread::sleep(std::time::Duration::from_secs(1));
-        }
-    });
+    // The mainloop thread must be gone before the context is dropped
+    mainloop.stop();
+    result
 }
 
-fn start_refresh_processor() {
-    // Take the receiver from the global channel
-    let receiver = REFRESH_CHANNEL.1.lock().unwrap().take();
-
-    if let Some(mut receiver) = receiver {
-        tokio::spawn(async move {
-            loop {
-                // Wait for first refresh request
-                if receiver.recv().await.is_none() {
-                    break;
-                }
-
-                // Debounce: wait 100ms and drain all pending refresh requests
-                tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
-
-                // Drain any additional refresh requests that came in during debounce period
-                while receiver.try_recv().is_ok() {
-                    // Just drain them
-                }
+fn watch_connection(context: &mut Context) -> Result<(), Box<dyn Error>> {
+    if context.connect(None, FlagSet::NOFLAGS, None).is_err() {
+        return Err("Failed to connect to PulseAudio".into());
+    }
 
-                println!("Processing debounced refresh request...");
-                match refresh_audio_applications().await {
-                    Ok(_) => println!("Audio applications refreshed successfully"),
-                    Err(e) => eprintln!("Failed to refresh audio applications: {:?}", e),
-                }
+    // Wait for connection
+    loop {
+        match context.get_state() {
+            State::Ready => break,
+            State::Failed => return Err("PulseAudio connection failed".into()),
+            State::Terminated => return Err("PulseAudio connection terminated".into()),
+            _ => {
+                std::thread::sleep(std::time::Duration::from_millis(50));
             }
-        });
+        }
+    }
+
+    // Subscribe to playback (sink input, sink), recording (source output, source)
+    // and default device (server, card) events
+    context.subscribe(
+        InterestMaskSet::SINK_INPUT
+            | InterestMaskSet::SINK
+            | InterestMaskSet::SOURCE_OUTPUT
+            | InterestMaskSet::SOURCE
+            | InterestMaskSet::SERVER
+            | InterestMaskSet::CARD,
+        |_success| {},
+    );
+
+    println!("PulseAudio monitoring started successfully");
+    connection_established();
+
+    // Keep the context and mainloop alive until the server goes away, e.g. on a restart
+    while context.get_state().is_good() {
+        std::thread::sleep(CONNECTION_CHECK_INTERVAL);
     }
-}
 
-pub async fn refresh_audio_applications() -> Result<(), Box<dyn std::error::Error>> {
-    // Get current applications (same logic as manual-detection)
-    let applications = {
-        let mut audio_system = audio::create();
-        audio_system
-            .list_applications()
-            .map_err(|e| format!("Error fetching applications: {:?}", e))?
-    };
-
-    // Get ignored apps list from shared settings
-    let ignored_apps = {
-        let settings = crate::plugin::SHARED_SETTINGS.lock().await;
-        settings.ignored_apps_list.clone()
-    };
-
-    // Update mixers and Stream Deck buttons
-    mixer::update_mixer_channels(applications, &ignored_apps).await;
-    utils::update_stream_deck_buttons().await;
-
-    Ok(())
+    Err("PulseAudio connection lost".into())
 }
@@ -0,0 +1,6 @@
+#[allow(clippy::module_inception)]
+pub mod simulated;
+pub mod simulated_monitor;
+
+pub use simulated::SimulatedAudioSystem;
+pub use simulated_monitor::{start_simulated_meters, start_simulated_monitoring};
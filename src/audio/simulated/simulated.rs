@@ -0,0 +1,697 @@
+use crate::audio::{
+    AppInfo, AudioDevice, AudioSystem, MAX_BOOST_VOLUME_PERCENT,
+    identity::resolve_app_id,
+    monitor::{EventFacility, EventKind, request_refresh, send_event},
+};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::sync::{LazyLock, Mutex};
+
+/// Path to a JSON simulation script, the built-in demo set is used when unset
+const SCRIPT_ENV_VAR: &str = "OA_VOLUME_CONTROLLER_SIMULATION";
+
+pub(super) static SCRIPT: LazyLock<SimulationScript> = LazyLock::new(load_script);
+
+static STATE: LazyLock<Mutex<SimulatedState>> =
+    LazyLock::new(|| Mutex::new(SimulatedState::new(&SCRIPT)));
+
+/// A fake application playing audio
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct SimulatedStream {
+    pub app_name: String,
+    /// What PulseAudio would call the sink input name, e.g. the tab or song title
+    pub media_name: Option<String>,
+    pub icon_name: Option<String>,
+    pub volume: f32,
+    pub mute: bool,
+    /// Recording stream (e.g. a voice chat app using the microphone)
+    pub input: bool,
+    /// Description of the output device the stream plays to, the default one when unset
+    pub device: Option<String>,
+    /// Paused by its app, it keeps its stream but plays nothing
+    pub paused: bool,
+    /// Executable of the fake app, its identity is taken from it when set
+    pub binary: Option<String>,
+    /// Media role of the stream, e.g. music or phone
+    pub role: Option<String>,
+}
+
+impl Default for SimulatedStream {
+    fn default() -> Self {
+        Self {
+            app_name: "app_stream".to_string(),
+            media_name: None,
+            icon_name: None,
+            volume: 100.0,
+            mute: false,
+            input: false,
+            device: None,
+            paused: false,
+            binary: None,
+            role: None,
+        }
+    }
+}
+
+/// A fake output device, only the default one is shown as the system mixer
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct SimulatedDevice {
+    /// Name the fake sound server knows the device by, the description when unset
+    pub name: Option<String>,
+    pub description: String,
+    pub volume: f32,
+    pub mute: bool,
+    pub default: bool,
+    /// Microphone instead of an output
+    pub input: bool,
+}
+
+impl Default for SimulatedDevice {
+    fn default() -> Self {
+        Self {
+            name: None,
+            description: "System Audio".to_string(),
+            volume: 100.0,
+            mute: false,
+            default: false,
+            input: false,
+        }
+    }
+}
+
+impl SimulatedDevice {
+    fn name(&self) -> String {
+        self.name
+            .clone()
+            .unwrap_or_else(|| self.description.clone())
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScriptedAction {
+    Add(SimulatedStream),
+    /// Removes every stream of the named app
+    Remove(String),
+    SetVolume {
+        app_name: String,
+        volume: f32,
+    },
+    SetMute {
+        app_name: String,
+        mute: bool,
+    },
+    SetPaused {
+        app_name: String,
+        paused: bool,
+    },
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ScriptedEvent {
+    /// Delay after the previous event
+    pub after_ms: u64,
+    #[serde(flatten)]
+    pub action: ScriptedAction,
+}
+
+/// Initial apps and devices plus a timeline of changes played back by the monitor
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct SimulationScript {
+    pub apps: Vec<SimulatedStream>,
+    pub devices: Vec<SimulatedDevice>,
+    pub events: Vec<ScriptedEvent>,
+    /// Start the timeline over once its last event ran
+    pub repeat: bool,
+}
+
+impl SimulationScript {
+    fn demo() -> Self {
+        let stream = |app_name: &str, media_name: &str, volume| SimulatedStream {
+            app_name: app_name.to_string(),
+            media_name: Some(media_name.to_string()),
+            icon_name: Some(app_name.to_string()),
+            volume,
+            mute: false,
+            input: false,
+            device: None,
+            paused: false,
+            binary: None,
+            role: None,
+        };
+
+        Self {
+            apps: vec![
+                stream("firefox", "YouTube", 80.0),
+                stream("spotify", "Spotify", 65.0),
+                stream("discord", "Voice Connection", 100.0),
+                SimulatedStream {
+                    input: true,
+                    ..stream("discord", "Microphone", 100.0)
+                },
+            ],
+            devices: vec![
+                SimulatedDevice {
+                    name: Some("alsa_output.pci-0000_00_1f.3.analog-stereo".to_string()),
+                    description: "Built-in Audio Analog Stereo".to_string(),
+                    volume: 60.0,
+                    default: true,
+                    ..Default::default()
+                },
+                SimulatedDevice {
+                    name: Some("alsa_output.usb-headset.analog-stereo".to_string()),
+                    description: "USB Headset".to_string(),
+                    volume: 40.0,
+                    ..Default::default()
+                },
+                SimulatedDevice {
+                    name: Some("alsa_input.pci-0000_00_1f.3.analog-stereo".to_string()),
+                    description: "Built-in Microphone".to_string(),
+                    volume: 80.0,
+                    default: true,
+                    input: true,
+                    ..Default::default()
+                },
+            ],
+            events: Vec::new(),
+            repeat: false,
+        }
+    }
+}
+
+fn load_script() -> SimulationScript {
+    let Ok(path) = std::env::var(SCRIPT_ENV_VAR) else {
+        return SimulationScript::demo();
+    };
+
+    let script = std::fs::read_to_string(&path)
+        .map_err(|e| e.to_string())
+        .and_then(|json| serde_json::from_str(&json).map_err(|e| e.to_string()));
+
+    match script {
+        Ok(script) => {
+            println!("Loaded simulation script from {}", path);
+            script
+        }
+        Err(e) => {
+            eprintln!(
+                "Failed to load simulation script {}: {}, using demo",
+                path, e
+            );
+            SimulationScript::demo()
+        }
+    }
+}
+
+struct SimulatedState {
+    streams: BTreeMap<u32, SimulatedStream>,
+    devices: BTreeMap<u32, SimulatedDevice>,
+    next_index: u32,
+}
+
+impl SimulatedState {
+    fn new(script: &SimulationScript) -> Self {
+        let mut state = Self {
+            streams: BTreeMap::new(),
+            devices: BTreeMap::new(),
+            next_index: 0,
+        };
+
+        for device in &script.devices {
+            let index = state.next_index();
+            state.devices.insert(index, device.clone());
+        }
+        for stream in &script.apps {
+            let index = state.next_index();
+            state.streams.insert(index, stream.clone());
+        }
+
+        state
+    }
+
+    fn next_index(&mut self) -> u32 {
+        let index = self.next_index;
+        self.next_index += 1;
+        index
+    }
+
+    fn volume_mut(&mut self, index: u32, is_device: bool) -> Result<(&mut f32, &mut bool), String> {
+        if is_device {
+            self.devices
+                .get_mut(&index)
+                .map(|device| (&mut device.volume, &mut device.mute))
+        } else {
+            self.streams
+                .get_mut(&index)
+                .map(|stream| (&mut stream.volume, &mut stream.mute))
+        }
+        .ok_or_else(|| format!("No simulated stream or device with index {}", index))
+    }
+
+    /// Playback or recording channels, optionally led by the default device of that direction
+    fn list_channels(&self, is_input: bool, with_default_device: bool) -> Vec<AppInfo> {
+        let mut res: Vec<AppInfo> = Vec::new();
+        let streams: Vec<(&u32, &SimulatedStream)> = self
+            .streams
+            .iter()
+            .filter(|(_, stream)| stream.input == is_input)
+            .collect();
+
+        let mut app_names: Vec<String> = streams
+            .iter()
+            .map(|(_, stream)| stream.app_name.to_lowercase())
+            .collect();
+
+        if with_default_device
+            && let Some((&index, device)) = self
+                .devices
+                .iter()
+                .find(|(_, device)| device.default && device.input == is_input)
+        {
+            app_names.push(device.description.clone());
+
+            let (sink_name, icon_name) = if is_input {
+                ("Microphone", "audio-input-microphone")
+            } else {
+                ("System Audio", "audio-card")
+            };
+
+            res.push(AppInfo {
+                uid: index,
+                app_id: device.name(),
+                app_name: device.description.clone(),
+                sink_name: Some(sink_name.to_string()),
+                mute: device.mute,
+                vol_percent: device.volume,
+                icon_name: Some(icon_name.to_string()),
+                is_device: true,
+                is_multi_sink_app: false,
+                is_input,
+                output_device: None,
+                is_paused: false,
+                grouped_uids: Vec::new(),
+                process_binary: None,
+                media_role: None,
+            });
+        }
+
+        let output_devices = self.list_devices(false);
+
+        res.extend(streams.into_iter().map(|(&index, stream)| {
+            let app_name = stream.app_name.to_lowercase();
+            let name_count = app_names.iter().filter(|&name| name == &app_name).count();
+
+            AppInfo {
+                uid: index,
+                app_id: resolve_app_id(|key| match key {
+                    "application.name" => Some(stream.app_name.clone()),
+                    "application.process.binary" => stream.binary.clone(),
+                    "media.role" => stream.role.clone(),
+                    _ => None,
+                }),
+                app_name,
+                sink_name: stream.media_name.clone(),
+                mute: stream.mute,
+                vol_percent: stream.volume,
+                icon_name: stream.icon_name.clone(),
+                is_device: false,
+                is_multi_sink_app: name_count > 1,
+                is_input,
+                output_device: if is_input {
+                    None
+                } else {
+                    output_devices
+                        .iter()
+                        .find(|device| match &stream.device {
+                            Some(description) => &device.description == description,
+                            None => device.is_default,
+                        })
+                        .cloned()
+                },
+                is_paused: stream.paused,
+                grouped_uids: Vec::new(),
+                process_binary: stream.binary.clone(),
+                media_role: stream.role.clone(),
+            }
+        }));
+
+        res
+    }
+
+    fn list_devices(&self, is_input: bool) -> Vec<AudioDevice> {
+        self.devices
+            .iter()
+            .filter(|(_, device)| device.input == is_input)
+            .map(|(&index, device)| AudioDevice {
+                index,
+                name: device.name(),
+                description: device.description.clone(),
+                icon_name: None,
+                is_default: device.default,
+            })
+            .collect()
+    }
+
+    fn apps_named<'a>(
+        &'a mut self,
+        app_name: &'a str,
+    ) -> impl Iterator<Item = &'a mut SimulatedStream> + 'a {
+        self.streams
+            .values_mut()
+            .filter(move |stream| stream.app_name == app_name)
+    }
+}
+
+/// Adds a stream and notifies the plugin, returns its index
+pub fn add_stream(stream: SimulatedStream) -> u32 {
+    let is_input = stream.input;
+    let index = {
+        let mut state = STATE.lock().unwrap();
+        let index = state.next_index();
+        state.streams.insert(index, stream);
+        index
+    };
+
+    println!("New audio application detected");
+    notify(index, false, is_input, EventKind::New);
+    index
+}
+
+/// Removes every stream of an app and notifies the plugin
+pub fn remove_app(app_name: &str) {
+    let removed = {
+        let mut state = STATE.lock().unwrap();
+        let before = state.streams.len();
+        state
+            .streams
+            .retain(|_, stream| stream.app_name != app_name);
+        before != state.streams.len()
+    };
+
+    if removed {
+        println!("Audio application removed");
+        request_refresh();
+    }
+}
+
+/// Volume and mute state of a fake app that is playing, used to fake its level
+pub fn stream_volume(index: u32) -> Option<(f32, bool)> {
+    let state = STATE.lock().unwrap();
+    state
+        .streams
+        .get(&index)
+        .filter(|stream| !stream.paused)
+        .map(|stream| (stream.volume, stream.mute))
+}
+
+pub fn set_app_volume(app_name: &str, volume: f32) {
+    STATE
+        .lock()
+        .unwrap()
+        .apps_named(app_name)
+        .for_each(|stream| stream.volume = volume.clamp(0.0, MAX_BOOST_VOLUME_PERCENT));
+
+    println!("Audio application volume/mute changed");
+    request_refresh();
+}
+
+pub fn set_app_mute(app_name: &str, mute: bool) {
+    STATE
+        .lock()
+        .unwrap()
+        .apps_named(app_name)
+        .for_each(|stream| stream.mute = mute);
+
+    println!("Audio application volume/mute changed");
+    request_refresh();
+}
+
+pub fn set_app_paused(app_name: &str, paused: bool) {
+    STATE
+        .lock()
+        .unwrap()
+        .apps_named(app_name)
+        .for_each(|stream| stream.paused = paused);
+
+    println!("Audio application paused/resumed");
+    request_refresh();
+}
+
+/// Report a volume or mute change of one fake app or device, like a sound server would
+fn notify_changed(index: u32, is_device: bool, is_input: bool) {
+    notify(index, is_device, is_input, EventKind::Changed);
+}
+
+fn notify(index: u32, is_device: bool, is_input: bool, kind: EventKind) {
+    let facility = match (is_device, is_input) {
+        (false, false) => EventFacility::PlaybackStream,
+        (false, true) => EventFacility::RecordingStream,
+        (true, false) => EventFacility::OutputDevice,
+        (true, true) => EventFacility::InputDevice,
+    };
+    send_event(facility, kind, index);
+}
+
+/// AudioSystem backed by in-memory fake apps, for demos and running without a sound server
+pub struct SimulatedAudioSystem;
+
+impl SimulatedAudioSystem {
+    pub fn new() -> Result<Self, Box<dyn Error>> {
+        Ok(Self)
+    }
+
+    fn change_volume(
+        &self,
+        index: u32,
+        delta: f32,
+        is_device: bool,
+        is_input: bool,
+    ) -> Result<(), Box<dyn Error>> {
+        {
+            let mut state = STATE.lock().unwrap();
+            let (volume, _) = state.volume_mut(index, is_device)?;
+            *volume = (*volume + delta).clamp(0.0, MAX_BOOST_VOLUME_PERCENT);
+        }
+
+        notify_changed(index, is_device, is_input);
+        Ok(())
+    }
+}
+
+impl AudioSystem for SimulatedAudioSystem {
+    fn list_applications(&mut self) -> Result<Vec<AppInfo>, Box<dyn Error>> {
+        let state = STATE.lock().unwrap();
+
+        // The default output is only shown if the global flag is set
+        let mut res = state.list_channels(false, crate::utils::should_show_system_mixer());
+
+        // Microphones and recording apps are opt-in
+        if crate::utils::should_show_input_channels() {
+            res.extend(state.list_channels(true, true));
+        }
+
+        Ok(res)
+    }
+
+    fn increase_volume(
+        &mut self,
+        app_index: u32,
+        percent: f64,
+        is_device: bool,
+        is_input: bool,
+    ) -> Result<(), Box<dyn Error>> {
+        self.change_volume(app_index, percent as f32 * 100.0, is_device, is_input)
+    }
+
+    fn decrease_volume(
+        &mut self,
+        app_index: u32,
+        percent: f64,
+        is_device: bool,
+        is_input: bool,
+    ) -> Result<(), Box<dyn Error>> {
+        self.change_volume(app_index, -(percent as f32) * 100.0, is_device, is_input)
+    }
+
+    fn mute_volume(
+        &mut self,
+        app_index: u32,
+        mute: bool,
+        is_device: bool,
+        is_input: bool,
+    ) -> Result<(), Box<dyn Error>> {
+        {
+            let mut state = STATE.lock().unwrap();
+            let (_, muted) = state.volume_mut(app_index, is_device)?;
+            *muted = mute;
+        }
+
+        notify_changed(app_index, is_device, is_input);
+        Ok(())
+    }
+
+    fn set_volume(
+        &mut self,
+        app_index: u32,
+        percent: f32,
+        is_device: bool,
+        is_input: bool,
+    ) -> Result<(), Box<dyn Error>> {
+        {
+            let mut state = STATE.lock().unwrap();
+            let (volume, _) = state.volume_mut(app_index, is_device)?;
+            *volume = percent.clamp(0.0, MAX_BOOST_VOLUME_PERCENT);
+        }
+
+        notify_changed(app_index, is_device, is_input);
+        Ok(())
+    }
+
+    fn get_volume(
+        &mut self,
+        app_index: u32,
+        is_device: bool,
+        _is_input: bool,
+    ) -> Result<f32, Box<dyn Error>> {
+        let mut state = STATE.lock().unwrap();
+        let (volume, _) = state.volume_mut(app_index, is_device)?;
+        Ok(*volume)
+    }
+
+    fn list_output_devices(&mut self) -> Result<Vec<AudioDevice>, Box<dyn Error>> {
+        Ok(STATE.lock().unwrap().list_devices(false))
+    }
+
+    fn list_input_devices(&mut self) -> Result<Vec<AudioDevice>, Box<dyn Error>> {
+        Ok(STATE.lock().unwrap().list_devices(true))
+    }
+
+    fn set_default_device(
+        &mut self,
+        device_index: u32,
+        is_input: bool,
+        move_streams: bool,
+    ) -> Result<(), Box<dyn Error>> {
+        {
+            let mut state = STATE.lock().unwrap();
+            if state
+                .devices
+                .get(&device_index)
+                .is_none_or(|device| device.input != is_input)
+            {
+                return Err(format!("No simulated device with index {}", device_index).into());
+            }
+
+            for (&index, device) in state.devices.iter_mut() {
+                if device.input == is_input {
+                    device.default = index == device_index;
+                }
+            }
+
+            // Streams without an explicit device follow the default one
+            if move_streams {
+                state
+                    .streams
+                    .values_mut()
+                    .filter(|stream| stream.input == is_input)
+                    .for_each(|stream| stream.device = None);
+            }
+        }
+
+        request_refresh();
+        Ok(())
+    }
+
+    fn move_to_sink(&mut self, app_index: u32, sink_index: u32) -> Result<(), Box<dyn Error>> {
+        {
+            let mut state = STATE.lock().unwrap();
+            let device = state
+                .devices
+                .get(&sink_index)
+                .filter(|device| !device.input)
+                .map(|device| device.description.clone())
+                .ok_or_else(|| format!("No simulated output device with index {}", sink_index))?;
+            let stream = state
+                .streams
+                .get_mut(&app_index)
+                .ok_or_else(|| format!("No simulated stream with index {}", app_index))?;
+            stream.device = Some(device);
+        }
+
+        request_refresh();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::{self, Backend};
+    use crate::mixer::{MIXER_CHANNELS, MixerChannel};
+    use std::collections::HashMap;
+    use std::time::Duration;
+
+    /// Waits for the debounced refresh to bring the mixer to the expected state
+    async fn wait_for_mixer(expected: impl Fn(&HashMap<u8, MixerChannel>) -> bool) {
+        for _ in 0..100 {
+            if expected(&*MIXER_CHANNELS.lock().await) {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        panic!("Mixer never reached the expected state");
+    }
+
+    async fn slot_of(uid: u32) -> Option<u8> {
+        MIXER_CHANNELS
+            .lock()
+            .await
+            .iter()
+            .find(|(_, channel)| channel.uid == uid && !channel.is_device)
+            .map(|(&slot, _)| slot)
+    }
+
+    fn app(app_name: &str) -> SimulatedStream {
+        SimulatedStream {
+            app_name: app_name.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    #[ignore = "needs OA_VOLUME_CONTROLLER_AUDIO_BACKEND=simulated"]
+    async fn scripted_apps_come_and_go_on_the_mixer() {
+        assert_eq!(audio::select_backend(&[]), Backend::Simulated);
+        audio::start_monitoring();
+
+        let first = add_stream(app("e2e-first"));
+        let second = add_stream(app("e2e-second"));
+        wait_for_mixer(|channels| {
+            [first, second]
+                .iter()
+                .all(|uid| channels.values().any(|channel| channel.uid == *uid))
+        })
+        .await;
+        let second_slot = slot_of(second).await;
+
+        // The app that stays keeps its column when the other one quits
+        remove_app("e2e-first");
+        wait_for_mixer(|channels| !channels.values().any(|channel| channel.uid == first)).await;
+        assert_eq!(slot_of(second).await, second_slot);
+
+        set_app_volume("e2e-second", 30.0);
+        wait_for_mixer(|channels| {
+            channels
+                .values()
+                .any(|channel| channel.uid == second && channel.vol_percent == 30.0)
+        })
+        .await;
+
+        remove_app("e2e-second");
+        wait_for_mixer(|channels| !channels.values().any(|channel| channel.uid == second)).await;
+    }
+}
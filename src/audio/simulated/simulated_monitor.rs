@@ -0,0 +1,81 @@
+use super::simulated::{self, SCRIPT, ScriptedAction};
+use crate::audio::{NORMAL_VOLUME_PERCENT, meter};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+// How often fake peaks are produced, about as often as a sound server sends them
+const PEAK_INTERVAL: Duration = Duration::from_millis(40);
+
+static MONITOR_STARTED: AtomicBool = AtomicBool::new(false);
+
+/// Plays back the simulation script's timeline. Changes made through the
+/// simulated audio system notify the plugin on their own.
+pub fn start_simulated_monitoring() {
+    if MONITOR_STARTED.load(Ordering::Acquire) {
+        return; // Already started
+    }
+
+    MONITOR_STARTED.store(true, Ordering::Release);
+
+    if SCRIPT.events.is_empty() {
+        println!("Simulated audio started without scripted events");
+        return;
+    }
+
+    std::thread::spawn(move || {
+        println!("Starting simulated audio timeline...");
+
+        loop {
+            for event in &SCRIPT.events {
+                std::thread::sleep(Duration::from_millis(event.after_ms));
+
+                match &event.action {
+                    ScriptedAction::Add(stream) => {
+                        simulated::add_stream(stream.clone());
+                    }
+                    ScriptedAction::Remove(app_name) => simulated::remove_app(app_name),
+                    ScriptedAction::SetVolume { app_name, volume } => {
+                        simulated::set_app_volume(app_name, *volume)
+                    }
+                    ScriptedAction::SetMute { app_name, mute } => {
+                        simulated::set_app_mute(app_name, *mute)
+                    }
+                    ScriptedAction::SetPaused { app_name, paused } => {
+                        simulated::set_app_paused(app_name, *paused)
+                    }
+                }
+            }
+
+            if !SCRIPT.repeat {
+                break;
+            }
+        }
+
+        println!("Simulated audio timeline finished");
+    });
+}
+
+/// Fakes a level for every fake app being sampled: a wobble scaled by its volume,
+/// silent while muted or paused
+pub fn start_simulated_meters() {
+    std::thread::spawn(move || {
+        let start = Instant::now();
+
+        loop {
+            std::thread::sleep(PEAK_INTERVAL);
+            let time = start.elapsed().as_secs_f32();
+
+            for uid in meter::watched_streams().into_keys() {
+                let Some((volume, mute)) = simulated::stream_volume(uid) else {
+                    continue;
+                };
+                if mute {
+                    continue;
+                }
+
+                let wobble = 0.55 + 0.45 * (time * 3.0 + uid as f32).sin() * (time * 7.3).cos();
+                meter::report_peak(uid, wobble * volume / NORMAL_VOLUME_PERCENT);
+            }
+        }
+    });
+}
@@ -0,0 +1,120 @@
+use super::{AudioSystem, monitor};
+use std::error::Error;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::LazyLock;
+use std::sync::mpsc;
+
+type Job = Box<dyn FnOnce(&mut Connection) + Send>;
+
+// Requests for the audio worker thread, which owns the one connection to the sound server
+static WORKER: LazyLock<mpsc::Sender<Job>> = LazyLock::new(spawn_worker);
+
+/// The worker's connection, opened on first use and reopened after the
+/// monitor reconnected to a restarted sound server
+#[derive(Default)]
+struct Connection {
+    audio_system: Option<Box<dyn AudioSystem>>,
+    generation: u64,
+}
+
+impl Connection {
+    fn get(&mut self) -> Result<&mut dyn AudioSystem, Box<dyn Error>> {
+        let generation = monitor::connection_generation();
+        if self.generation != generation {
+            self.audio_system = None;
+        }
+
+        if self.audio_system.is_none() {
+            self.audio_system = Some(super::connect()?);
+            self.generation = generation;
+        }
+
+        Ok(self.audio_system.as_deref_mut().unwrap())
+    }
+}
+
+fn spawn_worker() -> mpsc::Sender<Job> {
+    let (sender, receiver) = mpsc::channel::<Job>();
+
+    // Sound server connections are not Send, so the connection is made on the worker itself
+    std::thread::Builder::new()
+        .name("audio-worker".to_string())
+        .spawn(move || {
+            let mut connection = Connection::default();
+            for job in receiver {
+                // A panicking job must not take the worker, and every later request, down
+                // with it. Its requester sees the request dropped, and the connection it
+                // may have left half-used is opened again for the next job.
+                if panic::catch_unwind(AssertUnwindSafe(|| job(&mut connection))).is_err() {
+                    eprintln!("Audio request panicked, reconnecting for the next one");
+                    connection = Connection::default();
+                }
+            }
+        })
+        .expect("Failed to spawn the audio worker thread");
+
+    sender
+}
+
+fn send_job(job: Job) {
+    if WORKER.send(job).is_err() {
+        eprintln!("Audio worker is gone, dropping request");
+    }
+}
+
+/// Run `request` on the audio worker and wait for its result.
+/// Requests run one at a time, in the order they were made.
+pub async fn request<T, F>(request: F) -> Result<T, String>
+where
+    T: Send + 'static,
+    F: FnOnce(&mut dyn AudioSystem) -> Result<T, Box<dyn Error>> + Send + 'static,
+{
+    let (sender, receiver) = tokio::sync::oneshot::channel();
+
+    send_job(Box::new(move |connection| {
+        let result = connection
+            .get()
+            .and_then(request)
+            .map_err(|e| e.to_string());
+        let _ = sender.send(result);
+    }));
+
+    receiver
+        .await
+        .map_err(|_| "audio worker dropped the request".to_string())?
+}
+
+/// Queue `request` on the audio worker without waiting for it, e.g. for key presses.
+/// `request` reports its own failures.
+pub fn submit<F>(request: F)
+where
+    F: FnOnce(&mut dyn AudioSystem) + Send + 'static,
+{
+    send_job(Box::new(move |connection| match connection.get() {
+        Ok(audio_system) => request(audio_system),
+        Err(e) => eprintln!("Dropping audio request: {}", e),
+    }));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn worker_survives_a_panicking_request() {
+        // Jobs that don't touch the connection, so no sound server is needed
+        let (sender, receiver) = tokio::sync::oneshot::channel::<()>();
+        send_job(Box::new(move |_| {
+            let _sender = sender;
+            panic!("bad request");
+        }));
+        assert!(receiver.await.is_err());
+
+        // Later requests still run
+        let (sender, receiver) = tokio::sync::oneshot::channel();
+        send_job(Box::new(move |_| {
+            let _ = sender.send(42);
+        }));
+        assert_eq!(receiver.await, Ok(42));
+    }
+}
@@ -0,0 +1,296 @@
+use crate::audio::{self, AppInfo, meter, monitor};
+use crate::plugin::SHARED_SETTINGS;
+use crate::utils;
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{LazyLock, Mutex};
+use std::time::{Duration, Instant};
+
+/// Media role of streams that are calls
+const PHONE_ROLE: &str = "phone";
+/// How often calls are checked for starting or ending
+const CALL_CHECK_INTERVAL: Duration = Duration::from_millis(250);
+/// A call starts once one of its streams made a sound within this
+const CALL_START: Duration = Duration::from_secs(1);
+/// A call ends once all of its streams stayed quiet (e.g. nobody talking) for this
+const CALL_RELEASE: Duration = Duration::from_secs(10);
+
+/// Volume a playback stream had before it was ducked, and the volume it was ducked to
+#[derive(Clone, Copy, Debug)]
+struct DuckedStream {
+    original_percent: f32,
+    ducked_percent: f32,
+}
+
+// Playback streams lowered for the current call
+static DUCKED_STREAMS: LazyLock<Mutex<HashMap<u32, DuckedStream>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+// Playback streams whose volume was changed during the current call, left alone until it ends
+static ADJUSTED_STREAMS: LazyLock<Mutex<HashSet<u32>>> =
+    LazyLock::new(|| Mutex::new(HashSet::new()));
+
+static CALL_WATCH_STARTED: AtomicBool = AtomicBool::new(false);
+
+// Playback streams of calls, with when each last made a sound. New ones haven't yet.
+static CALL_STREAMS: LazyLock<Mutex<HashMap<u32, Option<Instant>>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+// Whether a call is going on, other apps are ducked while it is
+static CALL_ACTIVE: AtomicBool = AtomicBool::new(false);
+
+/// Whether a stream belongs to a call: it has the phone role or one of the call apps plays it
+fn is_call(app: &AppInfo, call_apps: &[String]) -> bool {
+    app.media_role.as_deref() == Some(PHONE_ROLE)
+        || call_apps.iter().any(|name| {
+            name.eq_ignore_ascii_case(&app.app_id)
+                || name.eq_ignore_ascii_case(&app.app_name)
+                || app
+                    .process_binary
+                    .as_deref()
+                    .is_some_and(|binary| name.eq_ignore_ascii_case(binary))
+        })
+}
+
+/// Whether a call is going on given when each of its streams last made a sound. It starts
+/// once one of them made a sound just now and only ends after all of them stayed quiet for
+/// `CALL_RELEASE`, so short pauses in a conversation don't restore and re-duck every app.
+fn is_call_on(
+    was_on: bool,
+    last_sounds: impl Iterator<Item = Option<Instant>>,
+    now: Instant,
+) -> bool {
+    let window = if was_on { CALL_RELEASE } else { CALL_START };
+    last_sounds
+        .flatten()
+        .any(|last_sound| now.duration_since(last_sound) < window)
+}
+
+/// Set the playback streams that are calls, returns whether a call is going on
+fn set_call_streams(uids: HashSet<u32>) -> bool {
+    {
+        let mut calls = CALL_STREAMS.lock().unwrap();
+        calls.retain(|uid, _| uids.contains(uid));
+        for &uid in &uids {
+            calls.entry(uid).or_insert(None);
+        }
+    }
+    // Calls are sampled to tell when they play
+    meter::keep_sampling(uids);
+
+    update_call_activity();
+    CALL_ACTIVE.load(Ordering::Acquire)
+}
+
+/// Work out whether a call is going on, returns whether that changed.
+/// Streams that can't be sampled (e.g. on PipeWire) make a sound for as long as they run.
+fn update_call_activity() -> bool {
+    let now = Instant::now();
+    let mut calls = CALL_STREAMS.lock().unwrap();
+
+    for (&uid, last_sound) in calls.iter_mut() {
+        let Some(activity) = meter::stream_activity(uid) else {
+            continue;
+        };
+        let heard = if activity.sampled {
+            activity.last_sound
+        } else {
+            (!activity.paused).then_some(now)
+        };
+        if heard > *last_sound {
+            *last_sound = heard;
+        }
+    }
+
+    let was_on = CALL_ACTIVE.load(Ordering::Acquire);
+    let active = is_call_on(was_on, calls.values().copied(), now);
+    drop(calls);
+
+    CALL_ACTIVE.swap(active, Ordering::AcqRel) != active
+}
+
+/// Checks the calls for starting or ending and refreshes the mixer when they do,
+/// which ducks or restores the other apps. Nothing is checked while ducking is turned off.
+pub fn start_call_watch() {
+    if CALL_WATCH_STARTED.swap(true, Ordering::AcqRel) {
+        return; // Already started
+    }
+
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(CALL_CHECK_INTERVAL).await;
+
+            // Turning ducking off refreshes the mixer, which ends the call
+            if !utils::should_auto_duck() {
+                continue;
+            }
+
+            if update_call_activity() {
+                println!(
+                    "Call {}",
+                    if CALL_ACTIVE.load(Ordering::Acquire) {
+                        "started"
+                    } else {
+                        "ended"
+                    }
+                );
+                monitor::request_refresh();
+            }
+        }
+    });
+}
+
+/// Lower the other playback streams while a call plays and give them their volume back
+/// once it ended. Streams whose volume changed during the call keep the new volume.
+/// Only a complete list (`all_streams`) starts or ends ducking, single changed streams
+/// are only checked for volume changes.
+pub async fn update_ducking(applications: &[AppInfo], all_streams: bool) {
+    let streams: Vec<&AppInfo> = applications
+        .iter()
+        .filter(|app| !app.is_device && !app.is_input)
+        .collect();
+
+    let call_active = if all_streams {
+        let call_apps = if utils::should_auto_duck() && utils::is_audio_connected() {
+            Some(SHARED_SETTINGS.lock().await.ducking_apps.clone())
+        } else {
+            None
+        };
+        let calls = match call_apps {
+            Some(call_apps) => streams
+                .iter()
+                .filter(|app| is_call(app, &call_apps))
+                .map(|app| app.uid)
+                .collect(),
+            None => HashSet::new(),
+        };
+        Some(set_call_streams(calls))
+    } else {
+        None
+    };
+
+    let mut volume_changes = Vec::new();
+    {
+        let mut ducked = DUCKED_STREAMS.lock().unwrap();
+        let mut adjusted = ADJUSTED_STREAMS.lock().unwrap();
+
+        if all_streams {
+            ducked.retain(|uid, _| streams.iter().any(|app| app.uid == *uid));
+            adjusted.retain(|uid| streams.iter().any(|app| app.uid == *uid));
+        }
+
+        // Volume changes made during the call are kept, whoever made them
+        for app in &streams {
+            if let Some(stream) = ducked.get(&app.uid)
+                && (app.vol_percent - stream.ducked_percent).abs() >= 0.5
+            {
+                println!("Volume of ducked app {} changed, keeping it", app.app_id);
+                ducked.remove(&app.uid);
+                adjusted.insert(app.uid);
+            }
+        }
+
+        match call_active {
+            Some(true) => {
+                let keep = 1.0 - utils::get_ducking_percent() as f32 / 100.0;
+                let calls = CALL_STREAMS.lock().unwrap();
+                for app in &streams {
+                    if calls.contains_key(&app.uid)
+                        || ducked.contains_key(&app.uid)
+                        || adjusted.contains(&app.uid)
+                    {
+                        continue;
+                    }
+
+                    let stream = DuckedStream {
+                        original_percent: app.vol_percent,
+                        ducked_percent: app.vol_percent * keep,
+                    };
+                    ducked.insert(app.uid, stream);
+                    volume_changes.push((app.uid, app.app_id.clone(), stream.ducked_percent));
+                }
+            }
+            Some(false) => {
+                for app in &streams {
+                    if let Some(stream) = ducked.remove(&app.uid) {
+                        volume_changes.push((app.uid, app.app_id.clone(), stream.original_percent));
+                    }
+                }
+                ducked.clear();
+                adjusted.clear();
+            }
+            None => {}
+        }
+    }
+
+    if volume_changes.is_empty() {
+        return;
+    }
+
+    audio::submit(move |audio_system| {
+        for (uid, app_id, vol_percent) in volume_changes {
+            match audio_system.set_volume(uid, vol_percent, false, false) {
+                Ok(()) => println!("Ducking set {} to {}%", app_id, vol_percent.round()),
+                Err(e) => println!("Warning: Failed to duck {}: {}", app_id, e),
+            }
+        }
+    });
+}
+
+/// Whether a playback stream is lowered for a call right now
+pub fn is_ducked(uid: u32) -> bool {
+    DUCKED_STREAMS.lock().unwrap().contains_key(&uid)
+}
+
+/// Forget the ducked streams, e.g. after the sound server restarted and reuses stream indices
+pub fn forget_streams() {
+    DUCKED_STREAMS.lock().unwrap().clear();
+    ADJUSTED_STREAMS.lock().unwrap().clear();
+    CALL_STREAMS.lock().unwrap().clear();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn silent_call_streams_do_not_start_a_call() {
+        let now = Instant::now();
+
+        assert!(!is_call_on(false, std::iter::empty(), now));
+        assert!(!is_call_on(false, [None, None].into_iter(), now));
+        assert!(is_call_on(false, [None, Some(now)].into_iter(), now));
+        // A sound from a while ago doesn't start one either
+        let earlier = now - CALL_START * 2;
+        assert!(!is_call_on(false, [Some(earlier)].into_iter(), now));
+    }
+
+    #[test]
+    fn calls_end_only_after_staying_quiet() {
+        let now = Instant::now();
+        let pause = now - CALL_START * 2;
+        let long_pause = now - CALL_RELEASE - Duration::from_millis(1);
+
+        assert!(is_call_on(true, [Some(pause)].into_iter(), now));
+        assert!(is_call_on(
+            true,
+            [Some(long_pause), Some(pause)].into_iter(),
+            now
+        ));
+        assert!(!is_call_on(true, [Some(long_pause)].into_iter(), now));
+        assert!(!is_call_on(true, [None].into_iter(), now));
+    }
+
+    #[test]
+    fn calls_are_found_by_role_or_app() {
+        let app = |app_id: &str, role: Option<&str>| AppInfo {
+            media_role: role.map(str::to_string),
+            ..AppInfo::test_stream(1, app_id)
+        };
+        let call_apps = vec!["Discord".to_string()];
+
+        assert!(is_call(&app("firefox", Some(PHONE_ROLE)), &[]));
+        assert!(is_call(&app("discord", None), &call_apps));
+        assert!(!is_call(&app("spotify", Some("music")), &call_apps));
+    }
+}
@@ -3,11 +3,26 @@ use base64::{Engine as _, engine::general_purpose};
 use image::{Rgba, RgbaImage};
 use std::collections::HashMap;
 use std::fmt;
+use std::hash::{DefaultHasher, Hash, Hasher};
 use std::io::Cursor;
 use std::sync::{LazyLock, Mutex, OnceLock};
 
+use crate::audio::NORMAL_VOLUME_PERCENT;
+
 static VOLUME_BAR_CACHE: OnceLock<Mutex<HashMap<String, String>>> = OnceLock::new();
 
+const TOUCH_STRIP_ICON_SIZE: u32 = 56;
+
+// Volume bars of microphones and recording apps are tinted to tell them apart from playback
+const OUTPUT_BAR_COLOR: Rgba<u8> = Rgba([255, 255, 255, 255]);
+const INPUT_BAR_COLOR: Rgba<u8> = Rgba([255, 120, 110, 255]);
+const MUTED_BAR_COLOR: Rgba<u8> = Rgba([128, 128, 128, 255]);
+// Part of the bar above 100%, where the volume is amplified
+const BOOST_BAR_COLOR: Rgba<u8> = Rgba([255, 170, 40, 255]);
+// Live level meter next to the volume bar and the track it fills
+const LEVEL_METER_COLOR: Rgba<u8> = Rgba([90, 220, 120, 255]);
+const LEVEL_METER_TRACK_COLOR: Rgba<u8> = Rgba([255, 255, 255, 40]);
+
 pub static TRANSPARENT_ICON: LazyLock<String> = LazyLock::new(|| {
     const ICON_SIZE: u32 = 144;
     let img = RgbaImage::from_pixel(ICON_SIZE, ICON_SIZE, Rgba([0, 0, 0, 0]));
@@ -24,6 +39,7 @@ pub static TRANSPARENT_ICON: LazyLock<String> = LazyLock::new(|| {
 enum BarPosition {
     Upper,
     Lower,
+    Single,
 }
 
 impl fmt::Display for BarPosition {
@@ -31,14 +47,62 @@ impl fmt::Display for BarPosition {
         match self {
             BarPosition::Upper => write!(f, "Upper"),
             BarPosition::Lower => write!(f, "Lower"),
+            BarPosition::Single => write!(f, "Single"),
         }
     }
 }
 
-/// Get data URI format for split volume bar images
-pub fn get_volume_bar_data_uri_split(volume_percent: f32) -> Result<(String, String)> {
-    let upper_key = generate_cache_key(volume_percent, BarPosition::Upper);
-    let lower_key = generate_cache_key(volume_percent, BarPosition::Lower);
+fn bar_color(is_input: bool) -> Rgba<u8> {
+    if is_input {
+        INPUT_BAR_COLOR
+    } else {
+        OUTPUT_BAR_COLOR
+    }
+}
+
+fn pointer_color(volume_percent: f32, is_input: bool) -> Rgba<u8> {
+    if volume_percent > NORMAL_VOLUME_PERCENT {
+        BOOST_BAR_COLOR
+    } else {
+        bar_color(is_input)
+    }
+}
+
+/// Recolor the drawn pixels inside a rectangle, keeping their alpha
+fn tint_region(img: &mut RgbaImage, x0: u32, y0: u32, x1: u32, y1: u32, color: Rgba<u8>) {
+    for py in y0..y1.min(img.height()) {
+        for px in x0..x1.min(img.width()) {
+            let pixel = img.get_pixel_mut(px, py);
+            if pixel[3] > 0 {
+                *pixel = Rgba([color[0], color[1], color[2], pixel[3]]);
+            }
+        }
+    }
+}
+
+/// Get data URI format for split volume bar images.
+/// `max_percent` is the volume at the top of the bar, anything above 100% is drawn as boost.
+/// `level` draws a level meter (0.0 - 1.0) next to the bar.
+pub fn get_volume_bar_data_uri_split(
+    volume_percent: f32,
+    max_percent: f32,
+    is_input: bool,
+    level: Option<f32>,
+) -> Result<(String, String)> {
+    let upper_key = generate_cache_key(
+        volume_percent,
+        max_percent,
+        BarPosition::Upper,
+        is_input,
+        level,
+    );
+    let lower_key = generate_cache_key(
+        volume_percent,
+        max_percent,
+        BarPosition::Lower,
+        is_input,
+        level,
+    );
 
     if let (Ok(Some(cached_upper)), Ok(Some(cached_lower))) = (
         get_cached_value_safe(&upper_key),
@@ -47,7 +111,8 @@ pub fn get_volume_bar_data_uri_split(volume_percent: f32) -> Result<(String, Str
         return Ok((cached_upper, cached_lower));
     }
 
-    let (top_base64, bottom_base64) = get_volume_bar_base64_split(volume_percent)?;
+    let (top_base64, bottom_base64) =
+        get_volume_bar_base64_split(volume_percent, max_percent, is_input, level)?;
     let top_data_uri = format!("data:image/png;base64,{}", top_base64);
     let bottom_data_uri = format!("data:image/png;base64,{}", bottom_base64);
 
@@ -59,6 +124,33 @@ pub fn get_volume_bar_data_uri_split(volume_percent: f32) -> Result<(String, Str
     Ok((top_data_uri, bottom_data_uri))
 }
 
+/// Get data URI for a volume bar fitting on a single key (compact layout)
+pub fn get_volume_bar_data_uri_single(
+    volume_percent: f32,
+    max_percent: f32,
+    is_input: bool,
+    level: Option<f32>,
+) -> Result<String> {
+    let key = generate_cache_key(
+        volume_percent,
+        max_percent,
+        BarPosition::Single,
+        is_input,
+        level,
+    );
+
+    if let Ok(Some(cached)) = get_cached_value_safe(&key) {
+        return Ok(cached);
+    }
+
+    let img = generate_volume_bar_single(volume_percent, max_percent, is_input, level);
+    let data_uri = format!("data:image/png;base64,{}", encode_png_base64(&img)?);
+
+    set_cached_value(key, data_uri.clone()).expect("Failed to cache single volume bar");
+
+    Ok(data_uri)
+}
+
 fn set_cached_value(key: String, value: String) -> Result<(), String> {
     match get_cache().lock() {
         Ok(mut cache) => {
@@ -73,8 +165,37 @@ fn get_cache() -> &'static Mutex<HashMap<String, String>> {
     VOLUME_BAR_CACHE.get_or_init(|| Mutex::new(HashMap::new()))
 }
 
-fn generate_cache_key(volume_percent: f32, position: BarPosition) -> String {
-    format!("vol_{:.1}_part_{}", volume_percent, position)
+fn generate_cache_key(
+    volume_percent: f32,
+    max_percent: f32,
+    position: BarPosition,
+    is_input: bool,
+    level: Option<f32>,
+) -> String {
+    // Levels come in a handful of steps, so they don't blow up the cache
+    format!(
+        "vol_{:.1}_of_{:.0}_part_{}_input_{}_level_{:.3?}",
+        volume_percent, max_percent, position, is_input, level
+    )
+}
+
+fn generate_touch_strip_cache_key(
+    icon_uri: &str,
+    volume_percent: f32,
+    max_percent: f32,
+    mute: bool,
+    is_input: bool,
+) -> String {
+    let mut hasher = DefaultHasher::new();
+    icon_uri.hash(&mut hasher);
+    format!(
+        "strip_{:x}_vol_{:.1}_of_{:.0}_mute_{}_input_{}",
+        hasher.finish(),
+        volume_percent,
+        max_percent,
+        mute,
+        is_input
+    )
 }
 
 fn get_cached_value_safe(key: &str) -> Result<Option<String>, String> {
@@ -134,7 +255,12 @@ fn volume_bar_distance(
 
 /// Generate a volume bar image spanning 2 Stream Deck icons (288x144 total)
 /// Returns (top_image, bottom_image) as separate 144x144 images
-pub fn generate_volume_bar_split(volume_percent: f32) -> (RgbaImage, RgbaImage) {
+pub fn generate_volume_bar_split(
+    volume_percent: f32,
+    max_percent: f32,
+    is_input: bool,
+    level: Option<f32>,
+) -> (RgbaImage, RgbaImage) {
     const ICON_WIDTH: u32 = 144;
     const ICON_HEIGHT: u32 = 144;
     const TOTAL_HEIGHT: u32 = 288;
@@ -142,15 +268,18 @@ pub fn generate_volume_bar_split(volume_percent: f32) -> (RgbaImage, RgbaImage)
     const BAR_HEIGHT: u32 = 240;
     const POINTER_RADIUS: u32 = 20;
     const OUTLINE_THICKNESS: u32 = 6;
+    const METER_WIDTH: u32 = 8;
+    // Clear of the volume pointer
+    const METER_OFFSET: u32 = 38;
 
     let mut full_img = RgbaImage::from_pixel(ICON_WIDTH, TOTAL_HEIGHT, Rgba([0, 0, 0, 0]));
 
     let bar_x = (ICON_WIDTH - BAR_WIDTH) / 2;
     let bar_y = (TOTAL_HEIGHT - BAR_HEIGHT) / 2;
 
-    let bar_fill = Rgba([255, 255, 255, 255]);
-    let bar_outline = Rgba([255, 255, 255, 255]);
-    let circle_outline = Rgba([255, 255, 255, 255]);
+    let bar_fill = bar_color(is_input);
+    let bar_outline = bar_color(is_input);
+    let circle_outline = pointer_color(volume_percent, is_input);
 
     draw_volume_bar_outline(
         &mut full_img,
@@ -164,7 +293,8 @@ pub fn generate_volume_bar_split(volume_percent: f32) -> (RgbaImage, RgbaImage)
     );
 
     // Calculate and draw the filled portion
-    let fill_height = ((volume_percent / 100.0) * BAR_HEIGHT as f32) as u32;
+    let fill_height =
+        ((volume_percent.clamp(0.0, max_percent) / max_percent) * BAR_HEIGHT as f32) as u32;
     let fill_y = bar_y + BAR_HEIGHT - fill_height;
 
     if fill_height > OUTLINE_THICKNESS {
@@ -177,6 +307,30 @@ pub fn generate_volume_bar_split(volume_percent: f32) -> (RgbaImage, RgbaImage)
         }
     }
 
+    // Everything above the 100% mark is the boost region
+    if max_percent > NORMAL_VOLUME_PERCENT {
+        let normal_height = (NORMAL_VOLUME_PERCENT / max_percent * BAR_HEIGHT as f32) as u32;
+        tint_region(
+            &mut full_img,
+            bar_x,
+            bar_y,
+            bar_x + BAR_WIDTH + 1,
+            bar_y + BAR_HEIGHT - normal_height,
+            BOOST_BAR_COLOR,
+        );
+    }
+
+    if let Some(level) = level {
+        draw_level_meter(
+            &mut full_img,
+            bar_x + BAR_WIDTH / 2 + METER_OFFSET,
+            bar_y,
+            METER_WIDTH,
+            BAR_HEIGHT,
+            level,
+        );
+    }
+
     // Draw the volume indicator circle
     let circle_x = bar_x + BAR_WIDTH / 2;
     let circle_y = fill_y;
@@ -205,6 +359,107 @@ pub fn generate_volume_bar_split(volume_percent: f32) -> (RgbaImage, RgbaImage)
     (top_img, bottom_img)
 }
 
+/// Generate a volume bar image fitting on a single Stream Deck icon (144x144)
+pub fn generate_volume_bar_single(
+    volume_percent: f32,
+    max_percent: f32,
+    is_input: bool,
+    level: Option<f32>,
+) -> RgbaImage {
+    const ICON_SIZE: u32 = 144;
+    const BAR_WIDTH: u32 = 20;
+    const BAR_HEIGHT: u32 = 112;
+    const POINTER_RADIUS: u32 = 14;
+    const OUTLINE_THICKNESS: u32 = 5;
+    const METER_WIDTH: u32 = 6;
+    // Clear of the volume pointer
+    const METER_OFFSET: u32 = 26;
+
+    let mut img = RgbaImage::from_pixel(ICON_SIZE, ICON_SIZE, Rgba([0, 0, 0, 0]));
+
+    let bar_x = (ICON_SIZE - BAR_WIDTH) / 2;
+    let bar_y = (ICON_SIZE - BAR_HEIGHT) / 2;
+
+    let bar_color = bar_color(is_input);
+
+    draw_volume_bar_outline(
+        &mut img,
+        bar_x,
+        bar_y,
+        BAR_WIDTH,
+        BAR_HEIGHT,
+        BAR_WIDTH / 2,
+        bar_color,
+        OUTLINE_THICKNESS,
+    );
+
+    // Calculate and draw the filled portion
+    let fill_height =
+        ((volume_percent.clamp(0.0, max_percent) / max_percent) * BAR_HEIGHT as f32) as u32;
+    let fill_y = bar_y + BAR_HEIGHT - fill_height;
+
+    if fill_height > OUTLINE_THICKNESS {
+        for py in fill_y.max(bar_y + OUTLINE_THICKNESS)..(bar_y + BAR_HEIGHT - OUTLINE_THICKNESS) {
+            for px in (bar_x + OUTLINE_THICKNESS)..(bar_x + BAR_WIDTH - OUTLINE_THICKNESS + 1) {
+                img.put_pixel(px, py, bar_color);
+            }
+        }
+    }
+
+    // Everything above the 100% mark is the boost region
+    if max_percent > NORMAL_VOLUME_PERCENT {
+        let normal_height = (NORMAL_VOLUME_PERCENT / max_percent * BAR_HEIGHT as f32) as u32;
+        tint_region(
+            &mut img,
+            bar_x,
+            bar_y,
+            bar_x + BAR_WIDTH + 1,
+            bar_y + BAR_HEIGHT - normal_height,
+            BOOST_BAR_COLOR,
+        );
+    }
+
+    if let Some(level) = level {
+        draw_level_meter(
+            &mut img,
+            bar_x + BAR_WIDTH / 2 + METER_OFFSET,
+            bar_y,
+            METER_WIDTH,
+            BAR_HEIGHT,
+            level,
+        );
+    }
+
+    draw_volume_pointer(
+        &mut img,
+        bar_x + BAR_WIDTH / 2,
+        fill_y,
+        POINTER_RADIUS,
+        Rgba([0, 0, 0, 255]),
+        pointer_color(volume_percent, is_input),
+        OUTLINE_THICKNESS,
+    );
+
+    img
+}
+
+/// Draw a thin vertical meter filled from the bottom up to `level` (0.0 - 1.0)
+fn draw_level_meter(img: &mut RgbaImage, x: u32, y: u32, width: u32, height: u32, level: f32) {
+    let fill_y = y + height - (level.clamp(0.0, 1.0) * height as f32) as u32;
+
+    for py in y..(y + height).min(img.height()) {
+        let color = if py >= fill_y {
+            LEVEL_METER_COLOR
+        } else {
+            LEVEL_METER_TRACK_COLOR
+        };
+
+        for px in x..(x + width).min(img.width()) {
+            img.put_pixel(px, py, color);
+        }
+    }
+}
+
 /// Draw a filled circle with outline and antialiasing
 fn draw_volume_pointer(
     img: &mut RgbaImage,
@@ -270,6 +525,7 @@ fn draw_volume_pointer(
 }
 
 /// Draw only the outline of a rounded rectangle with antialiasing
+#[allow(clippy::too_many_arguments)]
 fn draw_volume_bar_outline(
     img: &mut RgbaImage,
     x: u32,
@@ -336,18 +592,347 @@ fn draw_volume_bar_outline(
 }
 
 /// Get base64 encoded volume bar images for 2 vertical Stream Deck icons
-fn get_volume_bar_base64_split(volume_percent: f32) -> Result<(String, String)> {
-    let (top_img, bottom_img) = generate_volume_bar_split(volume_percent);
+fn get_volume_bar_base64_split(
+    volume_percent: f32,
+    max_percent: f32,
+    is_input: bool,
+    level: Option<f32>,
+) -> Result<(String, String)> {
+    let (top_img, bottom_img) =
+        generate_volume_bar_split(volume_percent, max_percent, is_input, level);
+
+    Ok((
+        encode_png_base64(&top_img)?,
+        encode_png_base64(&bottom_img)?,
+    ))
+}
+
+fn encode_png_base64(img: &RgbaImage) -> Result<String> {
+    let mut buffer = Vec::new();
+    let mut cursor = Cursor::new(&mut buffer);
+    img.write_to(&mut cursor, image::ImageFormat::Png)?;
+
+    Ok(general_purpose::STANDARD.encode(&buffer))
+}
+
+/// Get data URI for the touch strip segment above a Stream Deck+ dial
+pub fn get_touch_strip_data_uri(
+    icon_uri: &str,
+    volume_percent: f32,
+    max_percent: f32,
+    mute: bool,
+    is_input: bool,
+) -> Result<String> {
+    let key = generate_touch_strip_cache_key(icon_uri, volume_percent, max_percent, mute, is_input);
+
+    if let Ok(Some(cached)) = get_cached_value_safe(&key) {
+        return Ok(cached);
+    }
+
+    let icon = decode_icon_data_uri(icon_uri, TOUCH_STRIP_ICON_SIZE);
+    let img = generate_touch_strip(icon.as_ref(), volume_percent, max_percent, mute, is_input);
+    let data_uri = format!("data:image/png;base64,{}", encode_png_base64(&img)?);
+
+    set_cached_value(key, data_uri.clone()).expect("Failed to cache touch strip image");
+
+    Ok(data_uri)
+}
 
-    let mut top_buffer = Vec::new();
-    let mut top_cursor = Cursor::new(&mut top_buffer);
-    top_img.write_to(&mut top_cursor, image::ImageFormat::Png)?;
-    let top_base64 = general_purpose::STANDARD.encode(&top_buffer);
+/// Generate a touch strip segment (200x100) with the app icon on the left
+/// and a horizontal volume bar on the right
+pub fn generate_touch_strip(
+    icon: Option<&RgbaImage>,
+    volume_percent: f32,
+    max_percent: f32,
+    mute: bool,
+    is_input: bool,
+) -> RgbaImage {
+    const STRIP_WIDTH: u32 = 200;
+    const STRIP_HEIGHT: u32 = 100;
+    const ICON_X: u32 = 10;
+    const BAR_X: u32 = 82;
+    const BAR_WIDTH: u32 = 106;
+    const BAR_HEIGHT: u32 = 20;
+    const POINTER_RADIUS: u32 = 14;
+    const OUTLINE_THICKNESS: u32 = 4;
+
+    let mut img = RgbaImage::from_pixel(STRIP_WIDTH, STRIP_HEIGHT, Rgba([0, 0, 0, 0]));
+
+    if let Some(icon) = icon {
+        let icon_y = (STRIP_HEIGHT - icon.height()) / 2;
+        image::imageops::overlay(&mut img, icon, ICON_X as i64, icon_y as i64);
+    }
+
+    let bar_y = (STRIP_HEIGHT - BAR_HEIGHT) / 2;
+    let bar_color = if mute {
+        MUTED_BAR_COLOR
+    } else {
+        bar_color(is_input)
+    };
+
+    draw_volume_bar_outline(
+        &mut img,
+        BAR_X,
+        bar_y,
+        BAR_WIDTH,
+        BAR_HEIGHT,
+        BAR_HEIGHT / 2,
+        bar_color,
+        OUTLINE_THICKNESS,
+    );
+
+    // Calculate and draw the filled portion, growing left to right
+    let fill_width =
+        ((volume_percent.clamp(0.0, max_percent) / max_percent) * BAR_WIDTH as f32) as u32;
+    let fill_x = BAR_X + fill_width;
+
+    if fill_width > OUTLINE_THICKNESS {
+        for py in (bar_y + OUTLINE_THICKNESS)..(bar_y + BAR_HEIGHT - OUTLINE_THICKNESS) {
+            for px in (BAR_X + OUTLINE_THICKNESS)..fill_x.min(BAR_X + BAR_WIDTH - OUTLINE_THICKNESS)
+            {
+                img.put_pixel(px, py, bar_color);
+            }
+        }
+    }
+
+    // Everything right of the 100% mark is the boost region, muted bars stay grey
+    if max_percent > NORMAL_VOLUME_PERCENT && !mute {
+        let normal_width = (NORMAL_VOLUME_PERCENT / max_percent * BAR_WIDTH as f32) as u32;
+        tint_region(
+            &mut img,
+            BAR_X + normal_width,
+            bar_y,
+            BAR_X + BAR_WIDTH + 1,
+            bar_y + BAR_HEIGHT + 1,
+            BOOST_BAR_COLOR,
+        );
+    }
+
+    let pointer_color = if mute {
+        MUTED_BAR_COLOR
+    } else {
+        pointer_color(volume_percent, is_input)
+    };
+
+    draw_volume_pointer(
+        &mut img,
+        fill_x,
+        bar_y + BAR_HEIGHT / 2,
+        POINTER_RADIUS,
+        Rgba([0, 0, 0, 255]),
+        pointer_color,
+        OUTLINE_THICKNESS,
+    );
+
+    img
+}
+
+/// Overlay a recording badge on the bottom right corner of an app icon, used for
+/// microphones and recording apps. Returns a PNG data URI.
+pub fn add_input_badge(icon_uri: &str, mute: bool) -> Result<String> {
+    const ICON_SIZE: u32 = 144;
+    const BADGE_RADIUS: u32 = 22;
+    const BADGE_CENTER: u32 = ICON_SIZE - BADGE_RADIUS - 4;
+    const OUTLINE_THICKNESS: u32 = 5;
+
+    let mut img = decode_icon_data_uri(icon_uri, ICON_SIZE)
+        .ok_or_else(|| anyhow::anyhow!("Failed to decode icon"))?;
+
+    let badge_color = if mute {
+        MUTED_BAR_COLOR
+    } else {
+        INPUT_BAR_COLOR
+    };
+
+    draw_volume_pointer(
+        &mut img,
+        BADGE_CENTER,
+        BADGE_CENTER,
+        BADGE_RADIUS,
+        badge_color,
+        Rgba([0, 0, 0, 255]),
+        OUTLINE_THICKNESS,
+    );
+
+    Ok(format!(
+        "data:image/png;base64,{}",
+        encode_png_base64(&img)?
+    ))
+}
+
+/// Decode an icon data URI (as produced by `utils::get_app_icon_uri`) into a square image
+/// SVG icons are rasterized, raster icons are resized
+fn decode_icon_data_uri(data_uri: &str, size: u32) -> Option<RgbaImage> {
+    let (header, payload) = data_uri.strip_prefix("data:")?.split_once(";base64,")?;
+    let data = general_purpose::STANDARD.decode(payload).ok()?;
+
+    if header == "image/svg+xml" {
+        let tree = resvg::usvg::Tree::from_data(&data, &resvg::usvg::Options::default()).ok()?;
+        let mut pixmap = resvg::tiny_skia::Pixmap::new(size, size)?;
+        let tree_size = tree.size();
+        let scale = size as f32 / tree_size.width().max(tree_size.height());
+        resvg::render(
+            &tree,
+            resvg::tiny_skia::Transform::from_scale(scale, scale),
+            &mut pixmap.as_mut(),
+        );
+
+        let png = pixmap.encode_png().ok()?;
+        image::load_from_memory(&png).ok().map(|img| img.to_rgba8())
+    } else {
+        let img = image::load_from_memory(&data).ok()?;
+        Some(
+            img.resize(size, size, image::imageops::FilterType::Lanczos3)
+                .to_rgba8(),
+        )
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PageDirection {
+    Previous,
+    Next,
+}
+
+impl fmt::Display for PageDirection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PageDirection::Previous => write!(f, "Previous"),
+            PageDirection::Next => write!(f, "Next"),
+        }
+    }
+}
+
+/// Get data URI for a page indicator key (144x144) showing one dot per page
+pub fn get_page_indicator_data_uri(page: u8, page_count: u16) -> Result<String> {
+    get_page_indicator_data_uri_sized(144, 144, page, page_count)
+}
+
+/// Get data URI for a page indicator on a touch strip segment (200x100)
+pub fn get_touch_strip_page_indicator_data_uri(page: u8, page_count: u16) -> Result<String> {
+    get_page_indicator_data_uri_sized(200, 100, page, page_count)
+}
+
+fn get_page_indicator_data_uri_sized(
+    width: u32,
+    height: u32,
+    page: u8,
+    page_count: u16,
+) -> Result<String> {
+    let key = format!("page_{}_of_{}_{}x{}", page, page_count, width, height);
+
+    if let Ok(Some(cached)) = get_cached_value_safe(&key) {
+        return Ok(cached);
+    }
+
+    let img = generate_page_indicator(width, height, page, page_count);
+    let data_uri = format!("data:image/png;base64,{}", encode_png_base64(&img)?);
+
+    set_cached_value(key, data_uri.clone()).expect("Failed to cache page indicator");
+
+    Ok(data_uri)
+}
+
+/// Get data URI for a previous/next page key (144x144)
+pub fn get_page_arrow_data_uri(direction: PageDirection) -> Result<String> {
+    let key = format!("page_arrow_{}", direction);
+
+    if let Ok(Some(cached)) = get_cached_value_safe(&key) {
+        return Ok(cached);
+    }
 
-    let mut bottom_buffer = Vec::new();
-    let mut bottom_cursor = Cursor::new(&mut bottom_buffer);
-    bottom_img.write_to(&mut bottom_cursor, image::ImageFormat::Png)?;
-    let bottom_base64 = general_purpose::STANDARD.encode(&bottom_buffer);
+    let img = generate_page_arrow(direction);
+    let data_uri = format!("data:image/png;base64,{}", encode_png_base64(&img)?);
+
+    set_cached_value(key, data_uri.clone()).expect("Failed to cache page arrow");
+
+    Ok(data_uri)
+}
+
+/// Generate a row of dots, one per page, with the current page filled.
+/// The dots are placed slightly above the center to leave room for the title.
+pub fn generate_page_indicator(width: u32, height: u32, page: u8, page_count: u16) -> RgbaImage {
+    const MAX_DOTS: u32 = 5;
+    const DOT_RADIUS: u32 = 9;
+    const DOT_SPACING: u32 = 28;
+    const OUTLINE_THICKNESS: u32 = 3;
+
+    let mut img = RgbaImage::from_pixel(width, height, Rgba([0, 0, 0, 0]));
+
+    // Too many pages to draw individually, the title carries the page number
+    let dot_count = page_count as u32;
+    if dot_count == 0 || dot_count > MAX_DOTS {
+        return img;
+    }
+
+    let color = Rgba([255, 255, 255, 255]);
+    let row_width = (dot_count - 1) * DOT_SPACING;
+    let start_x = (width - row_width) / 2;
+    let center_y = height * 2 / 5;
+
+    for dot in 0..dot_count {
+        let fill = if dot == page as u32 {
+            color
+        } else {
+            Rgba([0, 0, 0, 0])
+        };
+
+        draw_volume_pointer(
+            &mut img,
+            start_x + dot * DOT_SPACING,
+            center_y,
+            DOT_RADIUS,
+            fill,
+            color,
+            OUTLINE_THICKNESS,
+        );
+    }
+
+    img
+}
+
+/// Generate an arrow pointing left (previous page) or right (next page)
+pub fn generate_page_arrow(direction: PageDirection) -> RgbaImage {
+    const ICON_SIZE: u32 = 144;
+    const ARROW_WIDTH: f32 = 44.0;
+    const ARROW_HEIGHT: f32 = 64.0;
+    const SAMPLES: u32 = 4;
+
+    let mut img = RgbaImage::from_pixel(ICON_SIZE, ICON_SIZE, Rgba([0, 0, 0, 0]));
+    let color = Rgba([255, 255, 255, 255]);
+
+    let center = ICON_SIZE as f32 / 2.0;
+    let (tip_x, base_x) = match direction {
+        PageDirection::Previous => (center - ARROW_WIDTH / 2.0, center + ARROW_WIDTH / 2.0),
+        PageDirection::Next => (center + ARROW_WIDTH / 2.0, center - ARROW_WIDTH / 2.0),
+    };
+    let half_height = ARROW_HEIGHT / 2.0;
+
+    // Supersample each pixel for antialiased edges
+    for py in 0..ICON_SIZE {
+        for px in 0..ICON_SIZE {
+            let mut covered = 0;
+            for sy in 0..SAMPLES {
+                for sx in 0..SAMPLES {
+                    let x = px as f32 + (sx as f32 + 0.5) / SAMPLES as f32;
+                    let y = py as f32 + (sy as f32 + 0.5) / SAMPLES as f32;
+
+                    // Fraction of the way from the base to the tip
+                    let t = (x - base_x) / (tip_x - base_x);
+                    if (0.0..=1.0).contains(&t) && (y - center).abs() <= half_height * (1.0 - t) {
+                        covered += 1;
+                    }
+                }
+            }
+
+            if covered > 0 {
+                let alpha = covered as f32 / (SAMPLES * SAMPLES) as f32;
+                let bg = img.get_pixel(px, py);
+                let blended = blend_colors(*bg, color, alpha);
+                img.put_pixel(px, py, blended);
+            }
+        }
+    }
 
-    Ok((top_base64, bottom_base64))
+    img
 }
@@ -1,9 +1,12 @@
 use openaction::OpenActionResult;
 
 mod audio;
+mod ducking;
 mod gfx;
 mod mixer;
 mod plugin;
+mod restore;
+mod scene;
 mod utils;
 
 #[tokio::main]
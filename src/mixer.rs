@@ -1,4 +1,10 @@
-use crate::utils::get_app_icon_uri;
+use crate::audio::{AppInfo, AudioDevice, meter};
+use crate::ducking;
+use crate::gfx;
+use crate::utils::{self, get_app_icon_uri};
+use glob::{MatchOptions, Pattern};
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::sync::LazyLock;
 use tokio::sync::Mutex;
@@ -8,7 +14,11 @@ pub struct MixerChannel {
     pub header_id: Option<String>,
     pub upper_vol_btn_id: Option<String>,
     pub lower_vol_btn_id: Option<String>,
+    pub output_btn_id: Option<String>,
+    pub dial_id: Option<String>,
     pub uid: u32,
+    /// Identity of the app, what ignoring, pinning and grouping go by
+    pub app_id: String,
     pub app_name: String,
     pub sink_name: Option<String>,
     pub mute: bool,
@@ -18,105 +28,705 @@ pub struct MixerChannel {
     pub uses_default_icon: bool,
     pub is_device: bool,
     pub is_multi_sink_app: bool,
+    /// Microphone or recording app
+    pub is_input: bool,
+    /// Output device a playback channel plays to
+    pub output_device: Option<AudioDevice>,
+    /// Pinned app that is not running, the channel only holds its name and icon
+    pub is_placeholder: bool,
+    /// App hidden for being paused or silent, it keeps its column greyed out until it plays
+    pub is_hidden: bool,
+    /// Current audio level (0.0 - 1.0) while level meters are shown
+    pub level: Option<f32>,
+    /// Other streams of the app this channel controls along with `uid` when streams are grouped
+    pub grouped_uids: Vec<u32>,
+    /// Volume and mute of each stream of a group, to work out the group's when one changes
+    pub stream_volumes: HashMap<u32, (f32, bool)>,
+    /// Lowered for a call that is going on
+    pub is_ducked: bool,
 }
 
+/// What an app filter rule is matched against
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum FilterField {
+    /// App name or identity
+    #[default]
+    Name,
+    /// Executable of the app
+    Binary,
+    /// Media role of the stream, e.g. event or phone
+    Role,
+    /// Output device the stream plays to, by name or description
+    Sink,
+    /// Stream (sink input) name, e.g. the tab or song title
+    Media,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PatternKind {
+    /// Shell style wildcards, e.g. `wine*`
+    #[default]
+    Glob,
+    Regex,
+}
+
+/// A user defined pattern hiding (or, in allow-list mode, showing) the apps it matches
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+#[serde(default)]
+pub struct FilterRule {
+    pub field: FilterField,
+    pub kind: PatternKind,
+    pub pattern: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum FilterMode {
+    /// Apps matching a rule are hidden
+    #[default]
+    Ignore,
+    /// Only apps matching a rule are shown
+    Allow,
+}
+
+enum Matcher {
+    Glob(Pattern),
+    Regex(Regex),
+}
+
+/// The ignore list and filter rules, compiled once per refresh
+pub struct AppFilter {
+    ignored_apps: Vec<String>,
+    rules: Vec<(FilterField, Matcher)>,
+    mode: FilterMode,
+}
+
+impl AppFilter {
+    /// Rules with invalid patterns are reported and left out
+    pub fn new(ignored_apps: &[String], rules: &[FilterRule], mode: FilterMode) -> Self {
+        let rules = rules
+            .iter()
+            .filter(|rule| !rule.pattern.trim().is_empty())
+            .filter_map(|rule| {
+                let pattern = rule.pattern.trim();
+                let matcher = match rule.kind {
+                    PatternKind::Glob => Pattern::new(pattern)
+                        .map(Matcher::Glob)
+                        .map_err(|e| e.to_string()),
+                    PatternKind::Regex => RegexBuilder::new(pattern)
+                        .case_insensitive(true)
+                        .build()
+                        .map(Matcher::Regex)
+                        .map_err(|e| e.to_string()),
+                };
+
+                match matcher {
+                    Ok(matcher) => Some((rule.field, matcher)),
+                    Err(e) => {
+                        eprintln!("Skipping invalid filter pattern '{}': {}", pattern, e);
+                        None
+                    }
+                }
+            })
+            .collect();
+
+        Self {
+            ignored_apps: ignored_apps.to_vec(),
+            rules,
+            mode,
+        }
+    }
+
+    /// Whether an app is left off the deck. Rules only apply to streams, the system
+    /// and microphone channels have their own switches.
+    pub fn is_hidden(&self, app: &AppInfo) -> bool {
+        if self.ignored_apps.iter().any(|name| app.is_named(name)) {
+            return true;
+        }
+        if app.is_device {
+            return false;
+        }
+
+        // An allow-list without valid rules would hide every app
+        if self.rules.is_empty() {
+            return false;
+        }
+
+        let matched = self
+            .rules
+            .iter()
+            .any(|(field, matcher)| field_values(app, *field).any(|value| matcher.matches(value)));
+
+        match self.mode {
+            FilterMode::Ignore => matched,
+            FilterMode::Allow => !matched,
+        }
+    }
+
+    /// Whether an app that isn't running would be hidden, as far as its name tells.
+    /// Used to leave out the placeholders of pinned apps the filter hides.
+    fn is_name_hidden(&self, name: &str) -> bool {
+        if self.ignored_apps.iter().any(|ignored| ignored == name) {
+            return true;
+        }
+        if self.rules.is_empty() {
+            return false;
+        }
+
+        let name_matched = self
+            .rules
+            .iter()
+            .any(|(field, matcher)| *field == FilterField::Name && matcher.matches(name));
+
+        match self.mode {
+            FilterMode::Ignore => name_matched,
+            // Rules on other fields could still let the app through once it runs
+            FilterMode::Allow => {
+                !name_matched
+                    && self
+                        .rules
+                        .iter()
+                        .all(|(field, _)| *field == FilterField::Name)
+            }
+        }
+    }
+
+    pub fn rule_count(&self) -> usize {
+        self.ignored_apps.len() + self.rules.len()
+    }
+}
+
+impl Matcher {
+    fn matches(&self, value: &str) -> bool {
+        match self {
+            Matcher::Glob(pattern) => pattern.matches_with(
+                value,
+                MatchOptions {
+                    case_sensitive: false,
+                    ..MatchOptions::new()
+                },
+            ),
+            Matcher::Regex(regex) => regex.is_match(value),
+        }
+    }
+}
+
+/// The values of an app a rule on `field` is matched against
+fn field_values(app: &AppInfo, field: FilterField) -> impl Iterator<Item = &str> {
+    let values = match field {
+        FilterField::Name => [Some(app.app_name.as_str()), Some(app.app_id.as_str())],
+        FilterField::Binary => [app.process_binary.as_deref(), None],
+        FilterField::Role => [app.media_role.as_deref(), None],
+        FilterField::Sink => match &app.output_device {
+            Some(device) => [
+                Some(device.name.as_str()),
+                Some(device.description.as_str()),
+            ],
+            None => [None, None],
+        },
+        FilterField::Media => [app.sink_name.as_deref(), None],
+    };
+    values.into_iter().flatten()
+}
+
+// Same value as PA_INVALID_INDEX, never a valid stream or device index
+const NOT_RUNNING_UID: u32 = u32::MAX;
+
 pub static MIXER_CHANNELS: LazyLock<Mutex<HashMap<u8, MixerChannel>>> =
     LazyLock::new(|| Mutex::const_new(HashMap::new()));
 
-pub async fn create_mixer_channels(
-    applications: Vec<crate::audio::audio_system::AppInfo>,
-    ignored_apps: &[String],
-) {
+/// Icons for an app's channel, microphones and recording apps get a badge
+/// to tell them apart from playback channels of the same app
+fn get_channel_icon_uri(app: &AppInfo) -> (String, String, bool) {
+    let (icon_uri, icon_uri_mute, uses_default_icon) =
+        get_app_icon_uri(app.icon_name.clone(), app.app_name.clone());
+
+    if !app.is_input {
+        return (icon_uri, icon_uri_mute, uses_default_icon);
+    }
+
+    (
+        gfx::add_input_badge(&icon_uri, false).unwrap_or(icon_uri),
+        gfx::add_input_badge(&icon_uri_mute, true).unwrap_or(icon_uri_mute),
+        uses_default_icon,
+    )
+}
+
+/// Outcome of applying a change event to the channels
+pub enum ChannelUpdate {
+    /// No channel shows the stream or nothing visible changed
+    Unchanged,
+    /// The channel in this slot was updated and its keys need redrawing
+    Updated(u8),
+    /// The change can move apps between slots (e.g. the stream got renamed),
+    /// the whole mixer has to be rebuilt
+    NeedsRefresh,
+}
+
+/// Slot of the channel showing a stream or device
+pub async fn find_channel(uid: u32, is_device: bool, is_input: bool) -> Option<u8> {
+    let channels = MIXER_CHANNELS.lock().await;
+
+    channels
+        .iter()
+        .find(|(_, channel)| {
+            !channel.is_placeholder
+                && (channel.uid == uid || channel.grouped_uids.contains(&uid))
+                && channel.is_device == is_device
+                && channel.is_input == is_input
+        })
+        .map(|(&slot, _)| slot)
+}
+
+/// Apply a changed stream or device to the channel showing it, leaving all other channels alone
+pub async fn update_mixer_channel(mut app: AppInfo) -> ChannelUpdate {
     let mut channels = MIXER_CHANNELS.lock().await;
 
-    let mut col_key = 0;
-    for app in applications.into_iter() {
-        if ignored_apps.contains(&app.app_name) {
-            println!("Skipping ignored app: {}", app.app_name);
-            continue;
+    let Some((&slot, channel)) = channels.iter_mut().find(|(_, channel)| {
+        channel.is_same_stream(&app)
+            || (!app.is_device
+                && channel.grouped_uids.contains(&app.uid)
+                && channel.is_input == app.is_input)
+    }) else {
+        return ChannelUpdate::Unchanged;
+    };
+
+    // The volume and mute shown for a group depend on all of its streams
+    if !channel.grouped_uids.is_empty() {
+        if channel.app_id != app.app_id {
+            return ChannelUpdate::NeedsRefresh;
         }
+        let Some((vol_percent, mute)) =
+            channel.update_group_stream(&app, utils::should_average_group_volume())
+        else {
+            return ChannelUpdate::NeedsRefresh;
+        };
 
-        let (icon_uri, icon_uri_mute, uses_default_icon) =
-            get_app_icon_uri(app.icon_name, app.app_name.clone());
+        // The other streams of a group only add to its volume and mute
+        if app.uid != channel.uid {
+            let is_ducked =
+                !channel.is_input && channel.stream_uids().into_iter().any(ducking::is_ducked);
+            if (channel.vol_percent - vol_percent).abs() <= 0.01
+                && channel.mute == mute
+                && channel.is_ducked == is_ducked
+            {
+                return ChannelUpdate::Unchanged;
+            }
 
-        channels.insert(
-            col_key as u8,
-            MixerChannel {
-                header_id: None,
-                upper_vol_btn_id: None,
-                lower_vol_btn_id: None,
-                uid: app.uid,
-                app_name: app.app_name.clone(),
-                sink_name: app.sink_name.clone(),
-                mute: app.mute,
-                vol_percent: app.vol_percent,
-                icon_uri,
-                icon_uri_mute,
-                uses_default_icon,
-                is_device: app.is_device,
-                is_multi_sink_app: app.is_multi_sink_app,
-            },
-        );
+            channel.vol_percent = vol_percent;
+            channel.mute = mute;
+            channel.is_ducked = is_ducked;
+            return ChannelUpdate::Updated(slot);
+        }
+
+        app.vol_percent = vol_percent;
+        app.mute = mute;
+        app.grouped_uids = channel.grouped_uids.clone();
+    }
 
-        col_key += 1;
+    // Ignore lists, filter rules and pins go by app identity and name
+    if channel.app_id != app.app_id || channel.app_name != app.app_name {
+        return ChannelUpdate::NeedsRefresh;
     }
+
+    // Only known when all streams are listed, and can't change without a new stream
+    app.is_multi_sink_app = channel.is_multi_sink_app;
+
+    if !channel.needs_update(&app) {
+        return ChannelUpdate::Unchanged;
+    }
+
+    channel.update_from(&app);
+    ChannelUpdate::Updated(slot)
 }
 
-pub async fn update_mixer_channels(
-    applications: Vec<crate::audio::audio_system::AppInfo>,
-    ignored_apps: &[String],
-) {
+/// Unbind the channels from their streams, e.g. after the sound server restarted and
+/// reuses stream indices. Apps keep their slot and get their icons looked up again.
+pub async fn forget_streams() {
     let mut channels = MIXER_CHANNELS.lock().await;
 
-    let mut col_key = 0;
+    for channel in channels
+        .values_mut()
+        .filter(|channel| !channel.is_placeholder)
+    {
+        channel.uid = NOT_RUNNING_UID;
+    }
+}
+
+/// Whether the stream, or any stream of the group, is lowered for a call
+fn is_app_ducked(app: &AppInfo) -> bool {
+    !app.is_device
+        && !app.is_input
+        && std::iter::once(&app.uid)
+            .chain(app.grouped_uids.iter())
+            .any(|&uid| ducking::is_ducked(uid))
+}
+
+/// What a channel slot is showing after slot assignment
+enum SlotContent {
+    App(Box<AppInfo>),
+    /// App hidden for being paused or silent, holding on to its slot
+    Hidden(Box<AppInfo>),
+    /// Pinned app that has no audio stream at the moment
+    NotRunning(String),
+}
+
+impl MixerChannel {
+    /// Every stream (or the device) the channel controls
+    pub fn stream_uids(&self) -> Vec<u32> {
+        std::iter::once(self.uid)
+            .chain(self.grouped_uids.iter().copied())
+            .collect()
+    }
+
+    /// Record the volume and mute of one stream of the group and work out the group's
+    /// from all of them. None when the stream is not part of the group.
+    fn update_group_stream(&mut self, app: &AppInfo, average_volume: bool) -> Option<(f32, bool)> {
+        *self.stream_volumes.get_mut(&app.uid)? = (app.vol_percent, app.mute);
+        Some(group_volume(
+            self.stream_volumes.values().copied(),
+            average_volume,
+        ))
+    }
+
+    /// Whether the channel is currently bound to this exact stream or device
+    fn is_same_stream(&self, app: &AppInfo) -> bool {
+        !self.is_placeholder
+            && self.uid == app.uid
+            && self.is_device == app.is_device
+            && self.is_input == app.is_input
+    }
+
+    /// Whether the channel shows something else than `app` currently is
+    fn needs_update(&self, app: &AppInfo) -> bool {
+        self.is_placeholder
+            || self.uid != app.uid
+            || self.app_id != app.app_id
+            || self.app_name != app.app_name
+            || self.sink_name != app.sink_name
+            || self.mute != app.mute
+            || (self.vol_percent - app.vol_percent).abs() > 0.01
+            || self.is_device != app.is_device
+            || self.is_multi_sink_app != app.is_multi_sink_app
+            || self.is_input != app.is_input
+            || self.output_device != app.output_device
+            || self.grouped_uids != app.grouped_uids
+            || self.is_ducked != is_app_ducked(app)
+    }
+
+    fn update_from(&mut self, app: &AppInfo) {
+        if self.uid != app.uid || self.is_input != app.is_input || self.is_placeholder {
+            let (icon_uri, icon_uri_mute, uses_default_icon) = get_channel_icon_uri(app);
+            self.icon_uri = icon_uri;
+            self.icon_uri_mute = icon_uri_mute;
+            self.uses_default_icon = uses_default_icon;
+        }
+
+        self.uid = app.uid;
+        self.app_id = app.app_id.clone();
+        self.app_name = app.app_name.clone();
+        self.sink_name = app.sink_name.clone();
+        self.mute = app.mute;
+        self.vol_percent = app.vol_percent;
+        self.is_device = app.is_device;
+        self.is_multi_sink_app = app.is_multi_sink_app;
+        self.is_input = app.is_input;
+        self.output_device = app.output_device.clone();
+        self.grouped_uids = app.grouped_uids.clone();
+        self.is_ducked = is_app_ducked(app);
+        self.is_placeholder = false;
+    }
+
+    /// Whether the channel belongs to the same app, even if it is a different stream.
+    /// The system and microphone channels stay the same channel when the default
+    /// device changes underneath them.
+    fn is_same_app(&self, app: &AppInfo) -> bool {
+        !self.is_placeholder
+            && (self.app_id == app.app_id || app.is_device)
+            && self.is_device == app.is_device
+            && self.is_input == app.is_input
+    }
+}
+
+/// Volume and mute a group shows for the volume and mute of its streams: muted when all
+/// of them are, with the loudest or the average volume of them
+fn group_volume(streams: impl Iterator<Item = (f32, bool)>, average_volume: bool) -> (f32, bool) {
+    let (mut count, mut sum, mut loudest, mut mute) = (0, 0.0, 0.0, true);
+    for (vol_percent, stream_mute) in streams {
+        count += 1;
+        sum += vol_percent;
+        loudest = f32::max(loudest, vol_percent);
+        mute &= stream_mute;
+    }
+
+    let vol_percent = if average_volume && count > 0 {
+        sum / count as f32
+    } else {
+        loudest
+    };
+    (vol_percent, mute)
+}
+
+/// Merge the streams of each app into the channel of its first stream, see `group_volume`
+/// for the volume and mute the group is shown with
+fn group_streams(applications: Vec<AppInfo>, average_volume: bool) -> Vec<AppInfo> {
+    let mut groups: Vec<(AppInfo, Vec<(f32, bool)>)> = Vec::new();
+
+    for mut app in applications {
+        if app.is_device {
+            groups.push((app, Vec::new()));
+            continue;
+        }
+
+        let group = groups.iter_mut().find(|(group, _)| {
+            !group.is_device && group.is_input == app.is_input && group.app_id == app.app_id
+        });
+
+        match group {
+            Some((group, volumes)) => {
+                group.grouped_uids.push(app.uid);
+                volumes.push((app.vol_percent, app.mute));
+            }
+            None => {
+                // Streams no longer share a name with another channel
+                app.is_multi_sink_app = false;
+                let volumes = vec![(app.vol_percent, app.mute)];
+                groups.push((app, volumes));
+            }
+        }
+    }
+
+    groups
+        .into_iter()
+        .map(|(mut app, volumes)| {
+            if volumes.len() > 1 {
+                (app.vol_percent, app.mute) = group_volume(volumes.into_iter(), average_volume);
+            }
+            app
+        })
+        .collect()
+}
+
+fn is_free_slot(
+    slot: u8,
+    slots: &HashMap<u8, SlotContent>,
+    pinned_apps: &HashMap<u8, String>,
+) -> bool {
+    !slots.contains_key(&slot) && !pinned_apps.contains_key(&slot)
+}
+
+/// Assign every application to a channel slot. Pinned apps go to the slot
+/// of their pinned column (or leave a placeholder there when not running).
+/// The remaining apps keep the slot they occupied in `previous`, either as
+/// the same stream or as a new stream of the same app, and new apps fill
+/// the lowest free slots. Hidden apps only hold on to their pinned slot or the
+/// slot their stream had, so they come back to the same column.
+fn assign_slots(
+    applications: Vec<AppInfo>,
+    hidden: Vec<AppInfo>,
+    filter: &AppFilter,
+    pinned_apps: &HashMap<u8, String>,
+    previous: &HashMap<u8, MixerChannel>,
+) -> HashMap<u8, SlotContent> {
+    let mut slots: HashMap<u8, SlotContent> = HashMap::new();
+    let mut unpinned = Vec::new();
+
+    // Pinned apps the filter hides get no placeholder either
+    let mut filtered_pins: Vec<u8> = pinned_apps
+        .iter()
+        .filter(|(_, name)| filter.is_name_hidden(name))
+        .map(|(&slot, _)| slot)
+        .collect();
+
     for app in applications {
-        if ignored_apps.contains(&app.app_name) {
-            println!("Skipping ignored app: {}", app.app_name);
+        if filter.is_hidden(&app) {
+            println!("Skipping filtered app: {}", app.app_id);
+            filtered_pins.extend(
+                pinned_apps
+                    .iter()
+                    .filter(|(_, name)| app.is_named(name))
+                    .map(|(&slot, _)| slot),
+            );
             continue;
         }
 
-        if let Some(channel) = channels.get_mut(&col_key) {
-            // Check if we need to update the channel
-            let needs_update = channel.uid != app.uid
-                || channel.app_name != app.app_name
-                || channel.sink_name != app.sink_name
-                || channel.mute != app.mute
-                || (channel.vol_percent - app.vol_percent).abs() > 0.01
-                || channel.is_device != app.is_device
-                || channel.is_multi_sink_app != app.is_multi_sink_app;
-
-            if needs_update {
-                if channel.uid != app.uid {
-                    let (icon_uri, icon_uri_mute, uses_default_icon) =
-                        get_app_icon_uri(app.icon_name, app.app_name.clone());
-                    channel.icon_uri = icon_uri;
-                    channel.icon_uri_mute = icon_uri_mute;
-                    channel.uses_default_icon = uses_default_icon;
-                }
+        let pinned_slot = pinned_apps
+            .iter()
+            .filter(|(slot, name)| app.is_named(name) && !slots.contains_key(slot))
+            .map(|(&slot, _)| slot)
+            .min();
 
-                // Update the channel data
-                channel.uid = app.uid;
-                channel.app_name = app.app_name;
-                channel.sink_name = app.sink_name;
-                channel.mute = app.mute;
-                channel.vol_percent = app.vol_percent;
-                channel.is_device = app.is_device;
-                channel.is_multi_sink_app = app.is_multi_sink_app;
+        match pinned_slot {
+            Some(slot) => {
+                slots.insert(slot, SlotContent::App(Box::new(app)));
+            }
+            None => unpinned.push(app),
+        }
+    }
+
+    let mut unpinned_hidden = Vec::new();
+    for app in hidden {
+        if filter.is_hidden(&app) {
+            filtered_pins.extend(
+                pinned_apps
+                    .iter()
+                    .filter(|(_, name)| app.is_named(name))
+                    .map(|(&slot, _)| slot),
+            );
+            continue;
+        }
+
+        let pinned_slot = pinned_apps
+            .iter()
+            .filter(|(slot, name)| app.is_named(name) && !slots.contains_key(slot))
+            .map(|(&slot, _)| slot)
+            .min();
+
+        match pinned_slot {
+            Some(slot) => {
+                slots.insert(slot, SlotContent::Hidden(Box::new(app)));
+            }
+            None => unpinned_hidden.push(app),
+        }
+    }
+
+    for (&slot, app_name) in pinned_apps {
+        if filtered_pins.contains(&slot) {
+            continue;
+        }
+        slots
+            .entry(slot)
+            .or_insert_with(|| SlotContent::NotRunning(app_name.clone()));
+    }
+
+    // Streams that still exist stay where they are, hidden ones included
+    let mut unplaced = Vec::new();
+    for app in unpinned {
+        let kept_slot = previous
+            .iter()
+            .find(|(slot, channel)| {
+                channel.is_same_stream(&app) && is_free_slot(**slot, &slots, pinned_apps)
+            })
+            .map(|(&slot, _)| slot);
+
+        match kept_slot {
+            Some(slot) => {
+                slots.insert(slot, SlotContent::App(Box::new(app)));
+            }
+            None => unplaced.push(app),
+        }
+    }
+
+    for app in unpinned_hidden {
+        let kept_slot = previous
+            .iter()
+            .find(|(slot, channel)| {
+                channel.is_same_stream(&app) && is_free_slot(**slot, &slots, pinned_apps)
+            })
+            .map(|(&slot, _)| slot);
+
+        if let Some(slot) = kept_slot {
+            slots.insert(slot, SlotContent::Hidden(Box::new(app)));
+        }
+    }
+
+    // A new stream of an app takes over the slot of its vanished stream
+    let mut new_apps = Vec::new();
+    for app in unplaced {
+        let reused_slot = previous
+            .iter()
+            .filter(|(slot, channel)| {
+                channel.is_same_app(&app) && is_free_slot(**slot, &slots, pinned_apps)
+            })
+            .map(|(&slot, _)| slot)
+            .min();
+
+        match reused_slot {
+            Some(slot) => {
+                slots.insert(slot, SlotContent::App(Box::new(app)));
+            }
+            None => new_apps.push(app),
+        }
+    }
+
+    let app_count = new_apps.len();
+    let mut free_slots = 0..=u8::MAX;
+    for (placed, app) in new_apps.into_iter().enumerate() {
+        let Some(slot) = free_slots.find(|&slot| is_free_slot(slot, &slots, pinned_apps)) else {
+            println!(
+                "Warning: Out of channel slots, {} apps are left off the mixer",
+                app_count - placed
+            );
+            break;
+        };
+        slots.insert(slot, SlotContent::App(Box::new(app)));
+    }
+
+    slots
+}
+
+pub async fn update_mixer_channels(
+    applications: Vec<AppInfo>,
+    filter: &AppFilter,
+    pinned_apps: &HashMap<u8, String>,
+) {
+    // Kept on grouped channels so a change of one of their streams updates them in place
+    let stream_volumes: HashMap<(bool, u32), (f32, bool)> = applications
+        .iter()
+        .filter(|app| !app.is_device)
+        .map(|app| ((app.is_input, app.uid), (app.vol_percent, app.mute)))
+        .collect();
+
+    let (mut hidden, mut applications): (Vec<AppInfo>, Vec<AppInfo>) =
+        applications.into_iter().partition(meter::is_inactive);
+
+    if utils::should_group_app_streams() {
+        let average_volume = utils::should_average_group_volume();
+        applications = group_streams(applications, average_volume);
+        hidden = group_streams(hidden, average_volume);
+    }
+
+    // An app that still plays on another stream needs no column for its hidden ones
+    hidden.retain(|app| {
+        !applications
+            .iter()
+            .any(|other| other.app_id == app.app_id && other.is_input == app.is_input)
+    });
+
+    let mut channels = MIXER_CHANNELS.lock().await;
+
+    let slots = assign_slots(applications, hidden, filter, pinned_apps, &channels);
+
+    for (col_key, content) in slots.iter() {
+        let (app, is_hidden) = match content {
+            SlotContent::App(app) => (app, false),
+            SlotContent::Hidden(app) => (app, true),
+            SlotContent::NotRunning(_) => continue,
+        };
+
+        if let Some(channel) = channels.get_mut(col_key) {
+            if channel.needs_update(app) || channel.is_hidden != is_hidden {
+                channel.update_from(app);
+                channel.is_hidden = is_hidden;
             }
         } else {
             // Insert new channel if it doesn't exist
-            let (icon_uri, icon_uri_mute, uses_default_icon) =
-                get_app_icon_uri(app.icon_name, app.app_name.clone());
+            let (icon_uri, icon_uri_mute, uses_default_icon) = get_channel_icon_uri(app);
 
             channels.insert(
-                col_key,
+                *col_key,
                 MixerChannel {
                     header_id: None,
                     upper_vol_btn_id: None,
                     lower_vol_btn_id: None,
+                    output_btn_id: None,
+                    dial_id: None,
                     uid: app.uid,
-                    app_name: app.app_name,
-                    sink_name: app.sink_name,
+                    app_id: app.app_id.clone(),
+                    app_name: app.app_name.clone(),
+                    sink_name: app.sink_name.clone(),
                     mute: app.mute,
                     vol_percent: app.vol_percent,
                     icon_uri,
@@ -124,18 +734,469 @@ pub async fn update_mixer_channels(
                     uses_default_icon,
                     is_device: app.is_device,
                     is_multi_sink_app: app.is_multi_sink_app,
+                    is_input: app.is_input,
+                    output_device: app.output_device.clone(),
+                    is_placeholder: false,
+                    is_hidden,
+                    level: None,
+                    grouped_uids: app.grouped_uids.clone(),
+                    stream_volumes: HashMap::new(),
+                    is_ducked: is_app_ducked(app),
                 },
             );
         }
+    }
 
-        col_key += 1;
+    for (col_key, content) in slots.iter() {
+        let SlotContent::NotRunning(app_name) = content else {
+            continue;
+        };
+
+        let up_to_date = channels
+            .get(col_key)
+            .is_some_and(|channel| channel.is_placeholder && &channel.app_name == app_name);
+        if up_to_date {
+            continue;
+        }
+
+        let (icon_uri, icon_uri_mute, uses_default_icon) = get_app_icon_uri(None, app_name.clone());
+
+        channels.insert(
+            *col_key,
+            MixerChannel {
+                header_id: None,
+                upper_vol_btn_id: None,
+                lower_vol_btn_id: None,
+                output_btn_id: None,
+                dial_id: None,
+                uid: NOT_RUNNING_UID,
+                app_id: app_name.clone(),
+                app_name: app_name.clone(),
+                sink_name: None,
+                mute: false,
+                vol_percent: 0.0,
+                icon_uri,
+                icon_uri_mute,
+                uses_default_icon,
+                is_device: false,
+                is_multi_sink_app: false,
+                is_input: false,
+                output_device: None,
+                is_placeholder: true,
+                is_hidden: false,
+                level: None,
+                grouped_uids: Vec::new(),
+                stream_volumes: HashMap::new(),
+                is_ducked: false,
+            },
+        );
     }
 
     // Remove channels that no longer have corresponding apps
-    channels.retain(|&key, _| key < col_key);
+    channels.retain(|key, _| slots.contains_key(key));
+
+    for channel in channels.values_mut() {
+        channel.stream_volumes = if channel.grouped_uids.is_empty() {
+            HashMap::new()
+        } else {
+            channel
+                .stream_uids()
+                .into_iter()
+                .filter_map(|uid| Some((uid, *stream_volumes.get(&(channel.is_input, uid))?)))
+                .collect()
+        };
+    }
 
     println!(
-        "Updated mixer channels (filtered {} ignored apps)",
-        ignored_apps.len()
+        "Updated mixer channels ({} ignored apps and filter rules, {} pinned apps)",
+        filter.rule_count(),
+        pinned_apps.len()
     );
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn no_filter() -> AppFilter {
+        AppFilter::new(&[], &[], FilterMode::Ignore)
+    }
+
+    /// The channel a slot assignment leaves behind for the next one
+    fn channel(app: &AppInfo) -> MixerChannel {
+        MixerChannel {
+            header_id: None,
+            upper_vol_btn_id: None,
+            lower_vol_btn_id: None,
+            output_btn_id: None,
+            dial_id: None,
+            uid: app.uid,
+            app_id: app.app_id.clone(),
+            app_name: app.app_name.clone(),
+            sink_name: app.sink_name.clone(),
+            mute: app.mute,
+            vol_percent: app.vol_percent,
+            icon_uri: String::new(),
+            icon_uri_mute: String::new(),
+            uses_default_icon: true,
+            is_device: app.is_device,
+            is_multi_sink_app: app.is_multi_sink_app,
+            is_input: app.is_input,
+            output_device: app.output_device.clone(),
+            is_placeholder: false,
+            is_hidden: false,
+            level: None,
+            grouped_uids: app.grouped_uids.clone(),
+            stream_volumes: HashMap::new(),
+            is_ducked: false,
+        }
+    }
+
+    fn channels(slots: &HashMap<u8, SlotContent>) -> HashMap<u8, MixerChannel> {
+        slots
+            .iter()
+            .filter_map(|(&slot, content)| match content {
+                SlotContent::App(app) | SlotContent::Hidden(app) => Some((slot, channel(app))),
+                SlotContent::NotRunning(_) => None,
+            })
+            .collect()
+    }
+
+    fn slot_of(slots: &HashMap<u8, SlotContent>, uid: u32) -> Option<u8> {
+        slots.iter().find_map(|(&slot, content)| match content {
+            SlotContent::App(app) if app.uid == uid => Some(slot),
+            _ => None,
+        })
+    }
+
+    #[test]
+    fn stream_keeps_its_slot_after_others_leave() {
+        let apps = vec![
+            AppInfo::test_stream(1, "firefox"),
+            AppInfo::test_stream(2, "spotify"),
+            AppInfo::test_stream(3, "discord"),
+        ];
+        let first = assign_slots(
+            apps,
+            Vec::new(),
+            &no_filter(),
+            &HashMap::new(),
+            &HashMap::new(),
+        );
+        assert_eq!(slot_of(&first, 3), Some(2));
+
+        // Spotify quits, Discord stays on its column instead of moving left
+        let apps = vec![
+            AppInfo::test_stream(1, "firefox"),
+            AppInfo::test_stream(3, "discord"),
+        ];
+        let second = assign_slots(
+            apps,
+            Vec::new(),
+            &no_filter(),
+            &HashMap::new(),
+            &channels(&first),
+        );
+        assert_eq!(slot_of(&second, 1), Some(0));
+        assert_eq!(slot_of(&second, 3), Some(2));
+        assert!(!second.contains_key(&1));
+
+        // The next new app fills the gap
+        let apps = vec![
+            AppInfo::test_stream(1, "firefox"),
+            AppInfo::test_stream(3, "discord"),
+            AppInfo::test_stream(4, "vlc"),
+        ];
+        let third = assign_slots(
+            apps,
+            Vec::new(),
+            &no_filter(),
+            &HashMap::new(),
+            &channels(&second),
+        );
+        assert_eq!(slot_of(&third, 4), Some(1));
+    }
+
+    #[test]
+    fn new_stream_takes_over_the_slot_of_its_app() {
+        let apps = vec![
+            AppInfo::test_stream(1, "firefox"),
+            AppInfo::test_stream(2, "spotify"),
+        ];
+        let first = assign_slots(
+            apps,
+            Vec::new(),
+            &no_filter(),
+            &HashMap::new(),
+            &HashMap::new(),
+        );
+
+        // Spotify restarted with a new stream, which goes back to the old column
+        let apps = vec![
+            AppInfo::test_stream(5, "spotify"),
+            AppInfo::test_stream(1, "firefox"),
+        ];
+        let second = assign_slots(
+            apps,
+            Vec::new(),
+            &no_filter(),
+            &HashMap::new(),
+            &channels(&first),
+        );
+        assert_eq!(slot_of(&second, 5), Some(1));
+        assert_eq!(slot_of(&second, 1), Some(0));
+    }
+
+    #[test]
+    fn pinned_column_holds_a_placeholder_while_not_running() {
+        let pinned = HashMap::from([(0, "spotify".to_string())]);
+        let apps = vec![AppInfo::test_stream(1, "firefox")];
+        let slots = assign_slots(apps, Vec::new(), &no_filter(), &pinned, &HashMap::new());
+
+        assert!(matches!(slots.get(&0), Some(SlotContent::NotRunning(name)) if name == "spotify"));
+        assert_eq!(slot_of(&slots, 1), Some(1));
+
+        // Once it plays it takes its column over from the placeholder
+        let apps = vec![
+            AppInfo::test_stream(1, "firefox"),
+            AppInfo::test_stream(2, "spotify"),
+        ];
+        let slots = assign_slots(apps, Vec::new(), &no_filter(), &pinned, &channels(&slots));
+        assert_eq!(slot_of(&slots, 2), Some(0));
+        assert_eq!(slot_of(&slots, 1), Some(1));
+    }
+
+    #[test]
+    fn extra_apps_overflow_onto_the_next_page() {
+        let apps = (1..=5)
+            .map(|uid| AppInfo::test_stream(uid, &format!("app{}", uid)))
+            .collect();
+        let slots = assign_slots(
+            apps,
+            Vec::new(),
+            &no_filter(),
+            &HashMap::new(),
+            &HashMap::new(),
+        );
+
+        // Three columns per page: the last two apps land on page 2
+        let column_map = HashMap::from([(0, 0), (1, 1), (2, 2)]);
+        let page_size = utils::get_page_size(&column_map);
+        assert_eq!(utils::get_page_count(&column_map, &channels(&slots)), 2);
+        assert_eq!(slot_of(&slots, 4).map(|slot| slot / page_size), Some(1));
+        assert_eq!(slot_of(&slots, 5).map(|slot| slot / page_size), Some(1));
+    }
+
+    #[test]
+    fn slots_run_out_without_overflowing() {
+        let apps = (1..=300)
+            .map(|uid| AppInfo::test_stream(uid, &format!("app{}", uid)))
+            .collect();
+        let slots = assign_slots(
+            apps,
+            Vec::new(),
+            &no_filter(),
+            &HashMap::new(),
+            &HashMap::new(),
+        );
+        assert_eq!(slots.len(), 256);
+
+        // One column per page: the last slot is on page 256
+        let column_map = HashMap::from([(0, 0)]);
+        assert_eq!(utils::get_page_count(&column_map, &channels(&slots)), 256);
+    }
+
+    #[test]
+    fn group_shows_the_loudest_or_average_volume() {
+        let streams = [(40.0, true), (80.0, false)];
+        assert_eq!(group_volume(streams.into_iter(), false), (80.0, false));
+        assert_eq!(group_volume(streams.into_iter(), true), (60.0, false));
+        assert_eq!(
+            group_volume([(40.0, true), (80.0, true)].into_iter(), false),
+            (80.0, true)
+        );
+    }
+
+    #[test]
+    fn grouped_stream_change_updates_the_group_in_place() {
+        let mut app = AppInfo::test_stream(1, "firefox");
+        app.grouped_uids = vec![2];
+        let mut group = channel(&app);
+        group.stream_volumes = HashMap::from([(1, (50.0, false)), (2, (80.0, false))]);
+
+        let mut second = AppInfo::test_stream(2, "firefox");
+        second.vol_percent = 20.0;
+        assert_eq!(
+            group.update_group_stream(&second, false),
+            Some((50.0, false))
+        );
+        assert_eq!(
+            group.update_group_stream(&second, true),
+            Some((35.0, false))
+        );
+
+        // Streams the group doesn't know about need the mixer rebuilt
+        let other = AppInfo::test_stream(3, "firefox");
+        assert_eq!(group.update_group_stream(&other, false), None);
+    }
+
+    #[test]
+    fn hidden_stream_holds_on_to_its_slot() {
+        let apps = vec![
+            AppInfo::test_stream(1, "firefox"),
+            AppInfo::test_stream(2, "spotify"),
+        ];
+        let first = assign_slots(
+            apps,
+            Vec::new(),
+            &no_filter(),
+            &HashMap::new(),
+            &HashMap::new(),
+        );
+
+        // Firefox goes idle, the new app doesn't take its column
+        let apps = vec![
+            AppInfo::test_stream(2, "spotify"),
+            AppInfo::test_stream(3, "vlc"),
+        ];
+        let hidden = vec![AppInfo::test_stream(1, "firefox")];
+        let second = assign_slots(
+            apps,
+            hidden,
+            &no_filter(),
+            &HashMap::new(),
+            &channels(&first),
+        );
+        assert!(matches!(second.get(&0), Some(SlotContent::Hidden(app)) if app.uid == 1));
+        assert_eq!(slot_of(&second, 3), Some(2));
+
+        // And plays on the same column again
+        let apps = vec![
+            AppInfo::test_stream(1, "firefox"),
+            AppInfo::test_stream(2, "spotify"),
+            AppInfo::test_stream(3, "vlc"),
+        ];
+        let third = assign_slots(
+            apps,
+            Vec::new(),
+            &no_filter(),
+            &HashMap::new(),
+            &channels(&second),
+        );
+        assert_eq!(slot_of(&third, 1), Some(0));
+    }
+
+    #[test]
+    fn hidden_pinned_app_is_not_shown_as_not_running() {
+        let pinned = HashMap::from([(0, "spotify".to_string())]);
+        let hidden = vec![AppInfo::test_stream(2, "spotify")];
+        let slots = assign_slots(Vec::new(), hidden, &no_filter(), &pinned, &HashMap::new());
+
+        assert!(matches!(slots.get(&0), Some(SlotContent::Hidden(app)) if app.uid == 2));
+    }
+
+    fn rule(field: FilterField, kind: PatternKind, pattern: &str) -> FilterRule {
+        FilterRule {
+            field,
+            kind,
+            pattern: pattern.to_string(),
+        }
+    }
+
+    #[test]
+    fn glob_rules_ignore_case() {
+        let filter = AppFilter::new(
+            &[],
+            &[rule(FilterField::Binary, PatternKind::Glob, "WINE*")],
+            FilterMode::Ignore,
+        );
+        let mut app = AppInfo::test_stream(1, "game");
+        app.process_binary = Some("wine-preloader".to_string());
+
+        assert!(filter.is_hidden(&app));
+        assert!(!filter.is_hidden(&AppInfo::test_stream(2, "wine")));
+    }
+
+    #[test]
+    fn regex_rules_ignore_case() {
+        let filter = AppFilter::new(
+            &[],
+            &[rule(
+                FilterField::Media,
+                PatternKind::Regex,
+                "^youtube|podcast$",
+            )],
+            FilterMode::Ignore,
+        );
+        let mut app = AppInfo::test_stream(1, "firefox");
+        app.sink_name = Some("YouTube - Music".to_string());
+        assert!(filter.is_hidden(&app));
+
+        app.sink_name = Some("Twitch".to_string());
+        assert!(!filter.is_hidden(&app));
+    }
+
+    #[test]
+    fn sink_rules_match_the_output_device() {
+        let filter = AppFilter::new(
+            &[],
+            &[rule(FilterField::Sink, PatternKind::Glob, "*headset*")],
+            FilterMode::Ignore,
+        );
+        let mut app = AppInfo::test_stream(1, "firefox");
+        app.sink_name = Some("Headset test".to_string());
+        assert!(!filter.is_hidden(&app));
+
+        app.output_device = Some(AudioDevice {
+            index: 3,
+            name: "alsa_output.usb-headset".to_string(),
+            description: "USB Audio".to_string(),
+            icon_name: None,
+            is_default: false,
+        });
+        assert!(filter.is_hidden(&app));
+    }
+
+    #[test]
+    fn allow_mode_shows_only_matching_apps() {
+        let filter = AppFilter::new(
+            &["spotify".to_string()],
+            &[rule(FilterField::Name, PatternKind::Glob, "s*")],
+            FilterMode::Allow,
+        );
+
+        assert!(!filter.is_hidden(&AppInfo::test_stream(1, "steam")));
+        assert!(filter.is_hidden(&AppInfo::test_stream(2, "firefox")));
+        // The ignore list wins in both modes
+        assert!(filter.is_hidden(&AppInfo::test_stream(3, "spotify")));
+    }
+
+    #[test]
+    fn empty_or_invalid_rules_hide_nothing() {
+        let rules = [
+            rule(FilterField::Name, PatternKind::Glob, "  "),
+            rule(FilterField::Name, PatternKind::Regex, "(unclosed"),
+            rule(FilterField::Name, PatternKind::Glob, "[unclosed"),
+        ];
+        for mode in [FilterMode::Ignore, FilterMode::Allow] {
+            let filter = AppFilter::new(&[], &rules, mode);
+            assert_eq!(filter.rule_count(), 0);
+            assert!(!filter.is_hidden(&AppInfo::test_stream(1, "firefox")));
+        }
+    }
+
+    #[test]
+    fn filtered_pinned_app_gets_no_placeholder() {
+        let pinned = HashMap::from([(0, "spotify".to_string()), (1, "firefox".to_string())]);
+        let filter = AppFilter::new(
+            &["spotify".to_string()],
+            &[rule(FilterField::Binary, PatternKind::Glob, "firefox-bin")],
+            FilterMode::Ignore,
+        );
+        let mut firefox = AppInfo::test_stream(1, "firefox");
+        firefox.process_binary = Some("firefox-bin".to_string());
+
+        let slots = assign_slots(vec![firefox], Vec::new(), &filter, &pinned, &HashMap::new());
+        assert!(slots.is_empty());
+    }
+}
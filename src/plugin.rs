@@ -1,18 +1,26 @@
 use openaction::*;
-use openaction::global_events::{GlobalEventHandler, DidReceiveGlobalSettingsEvent, set_global_event_handler};
+use openaction::global_events::{
+    DeviceDidConnectEvent, DidReceiveGlobalSettingsEvent, GlobalEventHandler, set_global_event_handler,
+};
 
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    audio::{self, pulse::pulse_monitor::refresh_audio_applications, *},
-    gfx::{self},
+    audio::{self, AudioDevice, monitor::refresh_audio_applications},
     mixer,
-    utils::{self, ButtonPressControl},
+    gfx::PageDirection,
+    restore::SavedVolume,
+    scene::{self, SceneEntry},
+    utils::{self, ButtonPressControl, ColumnLayout},
 };
 use std::{collections::HashMap, sync::LazyLock};
 use tokio::sync::Mutex;
 
 const VOLUME_INCREMENT: f64 = 0.1;
+const DIAL_VOLUME_INCREMENT: f64 = 0.02;
+const IGNORE_APP_LONG_PRESS_MS: u128 = 1000;
+const COMPACT_VOLUME_DOWN_PRESS_MS: u128 = 400;
+const CAPTURE_SCENE_LONG_PRESS_MS: u128 = 1000;
 
 pub static COLUMN_TO_CHANNEL_MAP: LazyLock<Mutex<HashMap<u8, u8>>> =
     LazyLock::new(|| Mutex::const_new(HashMap::new()));
@@ -23,17 +31,101 @@ pub static BUTTON_PRESS_CONTROL: LazyLock<Mutex<ButtonPressControl>> =
 pub static SHARED_SETTINGS: LazyLock<Mutex<VolumeControllerSettings>> =
     LazyLock::new(|| Mutex::const_new(VolumeControllerSettings::default()));
 
+/// Settings of every visible default device switcher, so monitor refreshes can redraw them
+pub static DEFAULT_DEVICE_SETTINGS: LazyLock<Mutex<HashMap<String, DefaultDeviceSettings>>> =
+    LazyLock::new(|| Mutex::const_new(HashMap::new()));
+
 #[derive(Serialize, Deserialize, Clone, Default)]
 #[serde(default)]
 pub struct VolumeControllerSettings {
     pub show_sys_mixer: bool,
+    /// Also show microphones and recording apps
+    pub show_input_channels: bool,
+    /// Let the volume keys and dials raise volumes up to 150%
+    pub allow_volume_boost: bool,
+    /// Volume both volume keys of a column snap to, 100% when unset
+    pub default_volume: Option<u8>,
+    /// Draw a live level meter next to the volume bars of playing apps
+    pub show_level_meters: bool,
+    /// Level meter updates per second, 10 when unset
+    pub level_meter_rate: Option<u8>,
+    /// Hide apps that are paused or silent until they play again
+    pub hide_inactive_apps: bool,
+    /// Seconds an app has to be paused or silent before it is hidden, 10 when unset
+    pub inactive_timeout: Option<u16>,
+    /// Show all streams of an app on one channel instead of a channel per stream
+    pub group_app_streams: bool,
+    /// Grouped channels show the average instead of the loudest volume of their streams
+    pub average_group_volume: bool,
+    /// Lower the other apps while a call (phone role stream or one of `ducking_apps`) plays
+    pub auto_ducking: bool,
+    /// Percent of their volume other apps are lowered by during calls, 50 when unset
+    pub ducking_percent: Option<u8>,
+    /// Apps whose streams count as calls, by name, identity or executable
+    pub ducking_apps: Vec<String>,
     pub ignored_apps_list: Vec<String>,
+    /// Patterns hiding (or with `filter_mode` allow, showing) the apps they match
+    pub app_filters: Vec<mixer::FilterRule>,
+    pub filter_mode: mixer::FilterMode,
+    /// SD column -> app name that should always live on that column
+    pub pinned_apps: HashMap<u8, String>,
+    /// SD column reserved for the page indicator and previous/next page keys
+    pub paging_column: Option<u8>,
+    /// App identity -> volume and mute the app last had, only kept in the global settings
+    #[serde(skip)]
+    pub app_volumes: HashMap<String, SavedVolume>,
+    /// Apps that don't get their remembered volume back when they restart
+    #[serde(skip)]
+    pub restore_opt_out: Vec<String>,
+    /// Scene name -> volumes recalled by scene keys, only kept in the global settings
+    #[serde(skip)]
+    pub scenes: HashMap<String, Vec<SceneEntry>>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Default)]
 #[serde(default)]
 pub struct GlobalPluginSettings {
     pub ignored_apps_list: Vec<String>,
+    pub app_filters: Vec<mixer::FilterRule>,
+    pub filter_mode: mixer::FilterMode,
+    pub pinned_apps: HashMap<u8, String>,
+    pub paging_column: Option<u8>,
+    pub app_volumes: HashMap<String, SavedVolume>,
+    pub restore_opt_out: Vec<String>,
+    pub scenes: HashMap<String, Vec<SceneEntry>>,
+}
+
+impl From<&VolumeControllerSettings> for GlobalPluginSettings {
+    fn from(settings: &VolumeControllerSettings) -> Self {
+        GlobalPluginSettings {
+            ignored_apps_list: settings.ignored_apps_list.clone(),
+            app_filters: settings.app_filters.clone(),
+            filter_mode: settings.filter_mode,
+            pinned_apps: settings.pinned_apps.clone(),
+            paging_column: settings.paging_column,
+            app_volumes: settings.app_volumes.clone(),
+            restore_opt_out: settings.restore_opt_out.clone(),
+            scenes: settings.scenes.clone(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct DefaultDeviceSettings {
+    /// Switch the default input (microphone) instead of the default output
+    pub is_input: bool,
+    /// Device made the default one on press, presses cycle through all devices when unset
+    pub device_name: Option<String>,
+    /// Also move the streams playing (or recording) on other devices to the new default
+    pub move_streams: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct SceneSettings {
+    /// Scene the key recalls on press and captures on long press
+    pub scene_name: String,
 }
 
 pub struct GlobalHandler;
@@ -47,17 +139,51 @@ impl GlobalEventHandler for GlobalHandler {
     }
 
     async fn did_receive_global_settings(&self, event: DidReceiveGlobalSettingsEvent) -> OpenActionResult<()> {
-        let global: GlobalPluginSettings = serde_json::from_value(event.payload.settings)
+        let mut global: GlobalPluginSettings = serde_json::from_value(event.payload.settings)
             .unwrap_or_default();
 
-        println!("did_receive_global_settings: {} ignored apps", global.ignored_apps_list.len());
+        // Pinned names are matched against lowercased app names, empty names unpin the column
+        global.pinned_apps = global
+            .pinned_apps
+            .into_iter()
+            .map(|(column, app_name)| (column, app_name.trim().to_lowercase()))
+            .filter(|(_, app_name)| !app_name.is_empty())
+            .collect();
+
+        println!(
+            "did_receive_global_settings: {} ignored apps, {} filter rules ({:?}), {} pinned apps",
+            global.ignored_apps_list.len(),
+            global.app_filters.len(),
+            global.filter_mode,
+            global.pinned_apps.len()
+        );
 
         let mut shared = SHARED_SETTINGS.lock().await;
-        if shared.ignored_apps_list != global.ignored_apps_list {
+
+        // Only used when apps restart or scenes are recalled, nothing to redraw
+        shared.app_volumes = global.app_volumes.clone();
+        shared.restore_opt_out = global.restore_opt_out.clone();
+        shared.scenes = global.scenes.clone();
+
+        let paging_column_changed = shared.paging_column != global.paging_column;
+        if shared.ignored_apps_list != global.ignored_apps_list
+            || shared.app_filters != global.app_filters
+            || shared.filter_mode != global.filter_mode
+            || shared.pinned_apps != global.pinned_apps
+            || paging_column_changed
+        {
             shared.ignored_apps_list = global.ignored_apps_list.clone();
+            shared.app_filters = global.app_filters.clone();
+            shared.filter_mode = global.filter_mode;
+            shared.pinned_apps = global.pinned_apps.clone();
+            shared.paging_column = global.paging_column;
             drop(shared);
 
-            // Sync ignored_apps_list into all instance settings
+            if paging_column_changed {
+                remap_columns(global.paging_column).await;
+            }
+
+            // Sync ignored_apps_list, the filters and pinned_apps into all instance settings
             let current = SHARED_SETTINGS.lock().await.clone();
             for inst in visible_instances(VolumeControllerAction::UUID).await {
                 let _ = inst.set_settings(&current).await;
@@ -68,6 +194,12 @@ impl GlobalEventHandler for GlobalHandler {
 
         Ok(())
     }
+
+    async fn device_did_connect(&self, _event: DeviceDidConnectEvent) -> OpenActionResult<()> {
+        // A (re)connected device starts out blank, whatever was sent to it before
+        utils::clear_render_cache().await;
+        Ok(())
+    }
 }
 
 pub struct VolumeControllerAction;
@@ -83,6 +215,7 @@ impl Action for VolumeControllerAction {
         _: &Self::Settings,
     ) -> OpenActionResult<()> {
         utils::cleanup_sd_column(instance).await;
+        utils::forget_render_state(&instance.instance_id).await;
 
         let Some(coords) = instance.coordinates else {
             println!("Warning: Instance {} has no coordinates", instance.instance_id);
@@ -100,19 +233,58 @@ impl Action for VolumeControllerAction {
         instance: &Instance,
         settings: &Self::Settings,
     ) -> OpenActionResult<()> {
-        println!("did_receive_settings for instance {}: show_sys_mixer={}",
-            instance.instance_id, settings.show_sys_mixer);
-
-        // Check if show_sys_mixer changed to avoid infinite loops
+        println!(
+            "did_receive_settings for instance {}: show_sys_mixer={}, show_input_channels={}, allow_volume_boost={}, default_volume={:?}, show_level_meters={}, level_meter_rate={:?}, hide_inactive_apps={}, inactive_timeout={:?}, group_app_streams={}, average_group_volume={}, auto_ducking={}, ducking_percent={:?}, ducking_apps={:?}",
+            instance.instance_id,
+            settings.show_sys_mixer,
+            settings.show_input_channels,
+            settings.allow_volume_boost,
+            settings.default_volume,
+            settings.show_level_meters,
+            settings.level_meter_rate,
+            settings.hide_inactive_apps,
+            settings.inactive_timeout,
+            settings.group_app_streams,
+            settings.average_group_volume,
+            settings.auto_ducking,
+            settings.ducking_percent,
+            settings.ducking_apps
+        );
+
+        // Check if any of the shared flags changed to avoid infinite loops
         let mut cached = SHARED_SETTINGS.lock().await;
-        let settings_changed = cached.show_sys_mixer != settings.show_sys_mixer;
+        let settings_changed = cached.show_sys_mixer != settings.show_sys_mixer
+            || cached.show_input_channels != settings.show_input_channels
+            || cached.allow_volume_boost != settings.allow_volume_boost
+            || cached.default_volume != settings.default_volume
+            || cached.show_level_meters != settings.show_level_meters
+            || cached.level_meter_rate != settings.level_meter_rate
+            || cached.hide_inactive_apps != settings.hide_inactive_apps
+            || cached.inactive_timeout != settings.inactive_timeout
+            || cached.group_app_streams != settings.group_app_streams
+            || cached.average_group_volume != settings.average_group_volume
+            || cached.auto_ducking != settings.auto_ducking
+            || cached.ducking_percent != settings.ducking_percent
+            || cached.ducking_apps != settings.ducking_apps;
 
         if settings_changed {
             println!("Settings changed, broadcasting to all instances");
             cached.show_sys_mixer = settings.show_sys_mixer;
+            cached.show_input_channels = settings.show_input_channels;
+            cached.allow_volume_boost = settings.allow_volume_boost;
+            cached.default_volume = settings.default_volume;
+            cached.show_level_meters = settings.show_level_meters;
+            cached.level_meter_rate = settings.level_meter_rate;
+            cached.hide_inactive_apps = settings.hide_inactive_apps;
+            cached.inactive_timeout = settings.inactive_timeout;
+            cached.group_app_streams = settings.group_app_streams;
+            cached.average_group_volume = settings.average_group_volume;
+            cached.auto_ducking = settings.auto_ducking;
+            cached.ducking_percent = settings.ducking_percent;
+            cached.ducking_apps = settings.ducking_apps.clone();
             drop(cached);
 
-            // Broadcast show_sys_mixer to all other instances
+            // Broadcast the shared flags to all other instances
             for inst in visible_instances(Self::UUID).await {
                 if inst.instance_id != instance.instance_id {
                     println!("Broadcasting to instance {}", inst.instance_id);
@@ -120,8 +292,19 @@ impl Action for VolumeControllerAction {
                 }
             }
 
-            // Apply show_sys_mixer setting
+            // Apply the shared flags
             utils::set_show_system_mixer(settings.show_sys_mixer);
+            utils::set_show_input_channels(settings.show_input_channels);
+            utils::set_allow_volume_boost(settings.allow_volume_boost);
+            utils::set_default_volume(settings.default_volume.unwrap_or(utils::DEFAULT_VOLUME_PERCENT));
+            utils::set_show_level_meters(settings.show_level_meters);
+            utils::set_level_meter_rate(settings.level_meter_rate.unwrap_or(utils::DEFAULT_LEVEL_METER_RATE));
+            utils::set_hide_inactive_apps(settings.hide_inactive_apps);
+            utils::set_inactive_timeout(settings.inactive_timeout.unwrap_or(utils::DEFAULT_INACTIVE_TIMEOUT_SECS));
+            utils::set_group_app_streams(settings.group_app_streams);
+            utils::set_average_group_volume(settings.average_group_volume);
+            utils::set_auto_duck(settings.auto_ducking);
+            utils::set_ducking_percent(settings.ducking_percent.unwrap_or(utils::DEFAULT_DUCKING_PERCENT));
             let _ = refresh_audio_applications().await;
         } else {
             drop(cached);
@@ -132,6 +315,9 @@ impl Action for VolumeControllerAction {
     }
 
     async fn will_appear(&self, instance: &Instance, _: &Self::Settings) -> OpenActionResult<()> {
+        // OpenDeck draws appearing keys from scratch, so nothing sent before is shown anymore
+        utils::forget_render_state(&instance.instance_id).await;
+
         // Sync with shared settings when appearing
         let shared = SHARED_SETTINGS.lock().await;
         let _ = instance.set_settings(&*shared).await;
@@ -142,14 +328,44 @@ impl Action for VolumeControllerAction {
             return Ok(());
         };
 
+        let sd_column = coords.column;
+
+        let paging_column = SHARED_SETTINGS.lock().await.paging_column;
+        if paging_column == Some(sd_column) {
+            let column_map = COLUMN_TO_CHANNEL_MAP.lock().await;
+            let channels = mixer::MIXER_CHANNELS.lock().await;
+            let page_count = utils::get_page_count(&column_map, &channels);
+            drop(channels);
+            drop(column_map);
+
+            let layout = utils::get_column_layout(instance).await;
+            utils::update_paging_key(instance, layout, page_count).await;
+            return Ok(());
+        }
+
         let mut column_map = COLUMN_TO_CHANNEL_MAP.lock().await;
         let mut channels = mixer::MIXER_CHANNELS.lock().await;
 
-        let sd_column = coords.column;
+        // A pinned column only gets its app once it has a channel slot, so refresh the mixer
+        if !column_map.contains_key(&sd_column)
+            && SHARED_SETTINGS.lock().await.pinned_apps.contains_key(&sd_column)
+        {
+            tokio::spawn(async {
+                let _ = refresh_audio_applications().await;
+            });
+        }
 
-        // Calculate next index before entry() call to avoid borrow checker issue
-        let next_index = column_map.len() as u8;
-        let channel_index = *column_map.entry(sd_column).or_insert(next_index);
+        // New columns take the first free position, positions of removed columns get reused
+        if !column_map.contains_key(&sd_column) {
+            let next_position = (0..=u8::MAX)
+                .find(|position| !column_map.values().any(|used| used == position))
+                .unwrap_or(u8::MAX);
+            column_map.insert(sd_column, next_position);
+        }
+
+        let Some(channel_index) = utils::get_channel_index(&column_map, sd_column) else {
+            return Ok(());
+        };
 
         let channel = match channels.get_mut(&channel_index) {
             Some(ch) => ch,
@@ -159,29 +375,24 @@ impl Action for VolumeControllerAction {
             }
         };
 
+        if utils::is_dial(instance) {
+            channel.dial_id = Some(instance.instance_id.clone());
+            utils::update_sd_dial(channel, instance).await;
+            return Ok(());
+        }
+
         match coords.row {
-            0 => {
-                utils::update_header(instance, channel).await;
-                channel.header_id = Some(instance.instance_id.clone());
-            }
-            1 | 2 => {
-                if let Ok((upper_img, lower_img)) =
-                    gfx::get_volume_bar_data_uri_split(channel.vol_percent)
-                {
-                    let img;
-                    if coords.row == 1 {
-                        channel.upper_vol_btn_id = Some(instance.instance_id.clone());
-                        img = upper_img;
-                    } else {
-                        channel.lower_vol_btn_id = Some(instance.instance_id.clone());
-                        img = lower_img;
-                    };
-                    instance.set_image(Some(img), None).await?;
-                }
-            }
+            0 => channel.header_id = Some(instance.instance_id.clone()),
+            1 => channel.upper_vol_btn_id = Some(instance.instance_id.clone()),
+            2 => channel.lower_vol_btn_id = Some(instance.instance_id.clone()),
+            3 => channel.output_btn_id = Some(instance.instance_id.clone()),
             _ => {} // Ignore other rows
         }
 
+        if let Some(layout) = utils::get_column_layout(instance).await {
+            utils::update_sd_column(channel, instance, layout).await;
+        }
+
         Ok(())
     }
 
@@ -192,12 +403,21 @@ impl Action for VolumeControllerAction {
     ) -> OpenActionResult<()> {
         let mut press_control = BUTTON_PRESS_CONTROL.lock().await;
 
+        // Volume keys step on release, unless both of them snapped the volume to the default
+        if let Some(coords) = instance.coordinates
+            && press_control.release_volume_key(coords.column, coords.row)
+        {
+            drop(press_control);
+            step_volume_key(coords.column, coords.row).await;
+            press_control = BUTTON_PRESS_CONTROL.lock().await;
+        }
+
         // Validate this is the correct button press
-        if let Some(action_id) = press_control.action_id.as_ref() {
-            if action_id != &instance.instance_id {
-                drop(press_control);
-                return Ok(());
-            }
+        if let Some(action_id) = press_control.action_id.as_ref()
+            && action_id != &instance.instance_id
+        {
+            drop(press_control);
+            return Ok(());
         }
 
         if let Some(duration_ms) = press_control.get_release_time() {
@@ -213,46 +433,76 @@ impl Action for VolumeControllerAction {
             };
             let sd_column = coords.column;
 
-            if duration_ms > 1000 && coords.row == 0 {
+            // Paging keys act on key_down
+            if is_paging_column(sd_column).await {
+                return Ok(());
+            }
+
+            // In the compact layout the single volume key acts on release:
+            // short press raises the volume, long press lowers it
+            if coords.row == 1
+                && utils::get_column_layout(instance).await == Some(ColumnLayout::Compact)
+            {
+                let column_map = COLUMN_TO_CHANNEL_MAP.lock().await;
+                let channels = mixer::MIXER_CHANNELS.lock().await;
+
+                let Some(channel) = utils::get_channel_index(&column_map, sd_column)
+                    .and_then(|channel_index| channels.get(&channel_index))
+                else {
+                    return Ok(());
+                };
+
+                if compact_press_lowers_volume(duration_ms) {
+                    decrease_volume(channel, VOLUME_INCREMENT);
+                } else {
+                    increase_volume(channel, VOLUME_INCREMENT);
+                }
+
+                return Ok(());
+            }
+
+            if duration_ms > IGNORE_APP_LONG_PRESS_MS && coords.row == 0 {
                 let column_map = COLUMN_TO_CHANNEL_MAP.lock().await;
                 let mut channels = mixer::MIXER_CHANNELS.lock().await;
 
                 // Look up the channel index for this SD column
-                let Some(&channel_index) = column_map.get(&sd_column) else {
+                let Some(channel_index) = utils::get_channel_index(&column_map, sd_column) else {
                     return Ok(());
                 };
 
-                if let Some(channel) = channels.get_mut(&channel_index) {
-                    let app_name = channel.app_name.clone();
-                    let uid = channel.uid;
+                if let Some(channel) = channels.get_mut(&channel_index)
+                    && !channel.is_placeholder
+                {
+                    let app_id = channel.app_id.clone();
+                    let uids = channel.stream_uids();
                     let is_device = channel.is_device;
+                    let is_input = channel.is_input;
 
                     channel.mute = false;
 
-                    // Drop locks before potentially blocking operations
+                    // Drop locks before saving the settings
                     drop(channels);
                     drop(column_map);
 
-                    {
-                        let mut audio_system = audio::create();
-                        if let Err(e) = audio_system.mute_volume(uid, false, is_device) {
-                            println!("Warning: Failed to unmute audio: {}", e);
+                    audio::submit(move |audio_system| {
+                        for uid in uids {
+                            if let Err(e) = audio_system.mute_volume(uid, false, is_device, is_input) {
+                                println!("Warning: Failed to unmute audio: {}", e);
+                            }
                         }
-                    } // audio_system is dropped here
+                    });
 
                     // Read cached shared settings, append app, and save back
                     let updated_settings = {
                         let mut shared_settings = SHARED_SETTINGS.lock().await;
-                        if !shared_settings.ignored_apps_list.contains(&app_name) {
-                            shared_settings.ignored_apps_list.push(app_name.clone());
+                        if !shared_settings.ignored_apps_list.contains(&app_id) {
+                            shared_settings.ignored_apps_list.push(app_id.clone());
                         }
                         shared_settings.clone()
                     };
 
                     // Save ignored apps to global settings
-                    let global = GlobalPluginSettings {
-                        ignored_apps_list: updated_settings.ignored_apps_list.clone(),
-                    };
+                    let global = GlobalPluginSettings::from(&updated_settings);
                     let _ = set_global_settings(global).await;
 
                     // Broadcast to ALL instances (including this one)
@@ -260,7 +510,7 @@ impl Action for VolumeControllerAction {
                         let _ = inst.set_settings(&updated_settings).await;
                     }
 
-                    println!("Added {} to ignored apps list and broadcast to all instances", app_name);
+                    println!("Added {} to ignored apps list and broadcast to all instances", app_id);
                 }
             }
         }
@@ -278,84 +528,695 @@ impl Action for VolumeControllerAction {
             return Ok(());
         };
 
+        let sd_column = coords.column;
+
+        if is_paging_column(sd_column).await {
+            if let Some(direction) = utils::get_paging_direction(instance) {
+                utils::turn_page(direction).await;
+            }
+            return Ok(());
+        }
+
+        let layout = utils::get_column_layout(instance).await;
+
+        // Pressing both volume keys of a column snaps the app to the default volume.
+        // Single volume keys are handled on key_up, as the other one may still follow.
+        let both_volume_keys = matches!(coords.row, 1 | 2)
+            && layout != Some(ColumnLayout::Compact)
+            && BUTTON_PRESS_CONTROL.lock().await.press_volume_key(sd_column, coords.row);
+
         let column_map = COLUMN_TO_CHANNEL_MAP.lock().await;
         let mut channels = mixer::MIXER_CHANNELS.lock().await;
 
-        let sd_column = coords.column;
-
         // Look up the channel index for this SD column
-        let Some(&channel_index) = column_map.get(&sd_column) else {
+        let Some(channel_index) = utils::get_channel_index(&column_map, sd_column) else {
             return Ok(());
         };
 
         if let Some(channel) = channels.get_mut(&channel_index) {
             match coords.row {
-                0 => {
-                    channel.mute = !channel.mute;
-                    let mut audio_system = audio::create();
-                    if let Err(e) = audio_system.mute_volume(channel.uid, channel.mute, channel.is_device) {
-                        println!("Warning: Failed to toggle mute for {}: {}", channel.app_name, e);
-                    } else {
-                        println!("Muting app {}", channel.app_name);
-                    }
-                }
-                1 => {
-                    let app_uid = channel.uid;
+                0 => toggle_mute(channel),
+                // Compact volume key is handled on key_up, once the press duration is known
+                1 if layout == Some(ColumnLayout::Compact) => {}
+                1 | 2 if both_volume_keys => reset_volume(channel),
+                3 if layout == Some(ColumnLayout::Full) => cycle_output_device(channel),
+                _ => {}
+            }
+        }
 
-                    if channel.vol_percent >= 100.0 {
-                        return Ok(());
-                    }
+        Ok(())
+    }
 
-                    let mut audio_system = audio::create();
-                    if let Err(e) = audio_system.increase_volume(app_uid, VOLUME_INCREMENT, channel.is_device) {
-                        println!("Warning: Failed to increase volume for {}: {}", channel.app_name, e);
-                    } else {
-                        println!(
-                            "Volume up in app {} {}",
-                            channel.app_name, channel.vol_percent
-                        );
-                    }
-                }
-                2 => {
-                    let app_uid = channel.uid;
-                    let mut audio_system = audio::create();
-                    if let Err(e) = audio_system.decrease_volume(app_uid, VOLUME_INCREMENT, channel.is_device) {
-                        println!("Warning: Failed to decrease volume for {}: {}", channel.app_name, e);
-                    } else {
-                        println!(
-                            "Volume down in app {} {}",
-                            channel.app_name, channel.vol_percent
-                        );
-                    }
-                }
-                _ => {}
+    async fn dial_rotate(
+        &self,
+        instance: &Instance,
+        _: &Self::Settings,
+        ticks: i16,
+        _pressed: bool,
+    ) -> OpenActionResult<()> {
+        let Some(coords) = instance.coordinates else {
+            println!("Warning: Instance {} has no coordinates", instance.instance_id);
+            return Ok(());
+        };
+
+        if is_paging_column(coords.column).await {
+            let direction = if ticks > 0 {
+                PageDirection::Next
+            } else {
+                PageDirection::Previous
+            };
+            utils::turn_page(direction).await;
+            return Ok(());
+        }
+
+        let column_map = COLUMN_TO_CHANNEL_MAP.lock().await;
+        let mut channels = mixer::MIXER_CHANNELS.lock().await;
+
+        let Some(channel_index) = utils::get_channel_index(&column_map, coords.column) else {
+            return Ok(());
+        };
+
+        if let Some(channel) = channels.get_mut(&channel_index) {
+            let step = DIAL_VOLUME_INCREMENT * ticks.unsigned_abs() as f64;
+            if ticks > 0 {
+                increase_volume(channel, step);
+            } else if ticks < 0 {
+                decrease_volume(channel, step);
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn dial_down(&self, instance: &Instance, _: &Self::Settings) -> OpenActionResult<()> {
+        let Some(coords) = instance.coordinates else {
+            println!("Warning: Instance {} has no coordinates", instance.instance_id);
+            return Ok(());
+        };
+
+        if is_paging_column(coords.column).await {
+            if let Some(direction) = utils::get_paging_direction(instance) {
+                utils::turn_page(direction).await;
+            }
+            return Ok(());
+        }
+
+        let column_map = COLUMN_TO_CHANNEL_MAP.lock().await;
+        let mut channels = mixer::MIXER_CHANNELS.lock().await;
+
+        let Some(channel_index) = utils::get_channel_index(&column_map, coords.column) else {
+            return Ok(());
+        };
+
+        if let Some(channel) = channels.get_mut(&channel_index) {
+            toggle_mute(channel);
+        }
+
+        Ok(())
+    }
+}
+
+pub struct DefaultDeviceAction;
+
+#[async_trait]
+impl Action for DefaultDeviceAction {
+    const UUID: ActionUuid = "com.victormarin.volume-controller.default-device";
+    type Settings = DefaultDeviceSettings;
+
+    async fn will_appear(&self, instance: &Instance, settings: &Self::Settings) -> OpenActionResult<()> {
+        utils::forget_render_state(&instance.instance_id).await;
+        DEFAULT_DEVICE_SETTINGS.lock().await.insert(instance.instance_id.clone(), settings.clone());
+        utils::update_default_device_key(instance, settings).await;
+        Ok(())
+    }
+
+    async fn will_disappear(&self, instance: &Instance, _: &Self::Settings) -> OpenActionResult<()> {
+        DEFAULT_DEVICE_SETTINGS.lock().await.remove(&instance.instance_id);
+        utils::forget_render_state(&instance.instance_id).await;
+        Ok(())
+    }
+
+    async fn did_receive_settings(
+        &self,
+        instance: &Instance,
+        settings: &Self::Settings,
+    ) -> OpenActionResult<()> {
+        DEFAULT_DEVICE_SETTINGS.lock().await.insert(instance.instance_id.clone(), settings.clone());
+        utils::update_default_device_key(instance, settings).await;
+        Ok(())
+    }
+
+    async fn key_down(&self, _instance: &Instance, settings: &Self::Settings) -> OpenActionResult<()> {
+        switch_default_device(settings);
+
+        // The system mixer channel and all switchers follow the new default device
+        let _ = refresh_audio_applications().await;
+        Ok(())
+    }
+
+    async fn property_inspector_did_appear(
+        &self,
+        instance: &Instance,
+        settings: &Self::Settings,
+    ) -> OpenActionResult<()> {
+        send_device_list(instance, settings.is_input).await;
+        Ok(())
+    }
+
+    async fn send_to_plugin(
+        &self,
+        instance: &Instance,
+        _: &Self::Settings,
+        payload: &serde_json::Value,
+    ) -> OpenActionResult<()> {
+        // The PI asks for the devices of a direction whenever it is switched
+        if let Some(is_input) = payload.get("is_input").and_then(serde_json::Value::as_bool) {
+            send_device_list(instance, is_input).await;
+        }
+        Ok(())
+    }
+}
+
+/// Send the output or input devices to the property inspector to pick from
+async fn send_device_list(instance: &Instance, is_input: bool) {
+    let devices = audio::request(move |audio_system| {
+        if is_input {
+            audio_system.list_input_devices()
+        } else {
+            audio_system.list_output_devices()
+        }
+    })
+    .await;
+
+    let devices: Vec<serde_json::Value> = devices
+        .unwrap_or_default()
+        .into_iter()
+        .map(|device| serde_json::json!({ "name": device.name, "description": device.description }))
+        .collect();
+
+    let _ = instance
+        .send_to_property_inspector(serde_json::json!({ "is_input": is_input, "devices": devices }))
+        .await;
+}
+
+/// Make the configured device, or the one after the current default, the default device
+fn switch_default_device(settings: &DefaultDeviceSettings) {
+    let settings = settings.clone();
+
+    audio::submit(move |audio_system| {
+        let devices = if settings.is_input {
+            audio_system.list_input_devices()
+        } else {
+            audio_system.list_output_devices()
+        };
+        let devices = match devices {
+            Ok(devices) => devices,
+            Err(e) => {
+                println!("Warning: Failed to list devices: {}", e);
+                return;
             }
+        };
+
+        let device_name = settings.device_name.as_deref();
+        let Some(device) = default_device_to_switch_to(&devices, device_name) else {
+            println!("Warning: No device to switch to");
+            return;
+        };
+
+        if device.is_default && !settings.move_streams {
+            return;
         }
 
+        if let Err(e) = audio_system.set_default_device(
+            device.index,
+            settings.is_input,
+            settings.move_streams,
+        ) {
+            println!("Warning: Failed to make {} the default device: {}", device.description, e);
+        } else {
+            println!("Default device set to {}", device.description);
+        }
+    });
+}
+
+pub struct SceneAction;
+
+#[async_trait]
+impl Action for SceneAction {
+    const UUID: ActionUuid = "com.victormarin.volume-controller.scene";
+    type Settings = SceneSettings;
+
+    async fn will_appear(&self, instance: &Instance, settings: &Self::Settings) -> OpenActionResult<()> {
+        utils::forget_render_state(&instance.instance_id).await;
+        utils::update_scene_key(instance, settings).await;
         Ok(())
     }
+
+    async fn will_disappear(&self, instance: &Instance, _: &Self::Settings) -> OpenActionResult<()> {
+        utils::forget_render_state(&instance.instance_id).await;
+        Ok(())
+    }
+
+    async fn did_receive_settings(
+        &self,
+        instance: &Instance,
+        settings: &Self::Settings,
+    ) -> OpenActionResult<()> {
+        utils::update_scene_key(instance, settings).await;
+        send_scene_list(instance, settings).await;
+        Ok(())
+    }
+
+    async fn key_down(&self, instance: &Instance, _: &Self::Settings) -> OpenActionResult<()> {
+        BUTTON_PRESS_CONTROL.lock().await.set_press_time(instance.instance_id.clone());
+        Ok(())
+    }
+
+    async fn key_up(&self, instance: &Instance, settings: &Self::Settings) -> OpenActionResult<()> {
+        let duration_ms = {
+            let mut press_control = BUTTON_PRESS_CONTROL.lock().await;
+            if press_control.action_id.as_ref() != Some(&instance.instance_id) {
+                return Ok(());
+            }
+            press_control.get_release_time()
+        };
+
+        // Long press captures the mixer into the scene, short press recalls it
+        if duration_ms.is_some_and(|duration_ms| duration_ms > CAPTURE_SCENE_LONG_PRESS_MS) {
+            capture_scene(instance, settings).await;
+        } else {
+            recall_scene(instance, settings).await;
+        }
+        Ok(())
+    }
+
+    async fn property_inspector_did_appear(
+        &self,
+        instance: &Instance,
+        settings: &Self::Settings,
+    ) -> OpenActionResult<()> {
+        send_scene_list(instance, settings).await;
+        Ok(())
+    }
+
+    async fn send_to_plugin(
+        &self,
+        instance: &Instance,
+        settings: &Self::Settings,
+        payload: &serde_json::Value,
+    ) -> OpenActionResult<()> {
+        let is_set = |key: &str| payload.get(key).and_then(serde_json::Value::as_bool) == Some(true);
+
+        if is_set("capture") {
+            capture_scene(instance, settings).await;
+        } else if is_set("delete") {
+            let scene_name = settings.scene_name.trim();
+            if let Err(e) = scene::delete_scene(scene_name).await {
+                eprintln!("Failed to delete scene {}: {}", scene_name, e);
+            }
+        }
+
+        send_scene_list(instance, settings).await;
+        Ok(())
+    }
+}
+
+/// Capture the mixer into the scene of a key, flashing the key to confirm
+async fn capture_scene(instance: &Instance, settings: &SceneSettings) {
+    let scene_name = settings.scene_name.trim();
+    if scene_name.is_empty() {
+        let _ = instance.show_alert().await;
+        return;
+    }
+
+    match scene::capture_scene(scene_name).await {
+        Ok(count) => {
+            println!("Captured {} channels into scene {}", count, scene_name);
+            let _ = instance.show_ok().await;
+        }
+        Err(e) => {
+            println!("Warning: Failed to capture scene {}: {}", scene_name, e);
+            let _ = instance.show_alert().await;
+        }
+    }
+}
+
+/// Recall the scene of a key, alerting when it was never captured
+async fn recall_scene(instance: &Instance, settings: &SceneSettings) {
+    let scene_name = settings.scene_name.trim();
+
+    match scene::recall_scene(scene_name).await {
+        Ok(count) => println!("Recalled scene {} on {} streams and devices", scene_name, count),
+        Err(e) => {
+            println!("Warning: Failed to recall scene {}: {}", scene_name, e);
+            let _ = instance.show_alert().await;
+        }
+    }
+}
+
+/// Send the captured scenes to the property inspector to pick from
+async fn send_scene_list(instance: &Instance, settings: &SceneSettings) {
+    let scenes: Vec<serde_json::Value> = {
+        let shared = SHARED_SETTINGS.lock().await;
+        let mut names: Vec<&String> = shared.scenes.keys().collect();
+        names.sort_unstable();
+        names
+            .into_iter()
+            .map(|name| serde_json::json!({ "name": name, "entries": shared.scenes[name].len() }))
+            .collect()
+    };
+
+    let _ = instance
+        .send_to_property_inspector(serde_json::json!({
+            "scene_name": settings.scene_name.trim(),
+            "scenes": scenes,
+        }))
+        .await;
+}
+
+async fn is_paging_column(sd_column: u8) -> bool {
+    SHARED_SETTINGS.lock().await.paging_column == Some(sd_column)
+}
+
+/// Rebuild the column map in SD column order, leaving out the paging column
+async fn remap_columns(paging_column: Option<u8>) {
+    let mut columns: Vec<u8> = visible_instances(VolumeControllerAction::UUID)
+        .await
+        .iter()
+        .filter_map(|instance| instance.coordinates.map(|coords| coords.column))
+        .filter(|&column| Some(column) != paging_column)
+        .collect();
+    columns.sort_unstable();
+    columns.dedup();
+
+    let mut column_map = COLUMN_TO_CHANNEL_MAP.lock().await;
+    column_map.clear();
+    for (position, column) in columns.into_iter().enumerate() {
+        column_map.insert(column, position as u8);
+    }
+
+    utils::set_current_page(0);
+}
+
+fn toggle_mute(channel: &mut mixer::MixerChannel) {
+    if channel.is_placeholder {
+        return;
+    }
+
+    channel.mute = !channel.mute;
+    let (uids, mute, is_device, is_input) =
+        (channel.stream_uids(), channel.mute, channel.is_device, channel.is_input);
+    let app_name = channel.app_name.clone();
+
+    audio::submit(move |audio_system| {
+        for uid in uids {
+            if let Err(e) = audio_system.mute_volume(uid, mute, is_device, is_input) {
+                println!("Warning: Failed to toggle mute for {}: {}", app_name, e);
+                return;
+            }
+        }
+        println!("Muting app {}", app_name);
+    });
+}
+
+fn increase_volume(channel: &mixer::MixerChannel, step: f64) {
+    let max_percent = utils::get_max_volume_percent();
+    if channel.is_placeholder || channel.vol_percent >= max_percent {
+        return;
+    }
+
+    let (uids, is_device, is_input) = (channel.stream_uids(), channel.is_device, channel.is_input);
+    let (app_name, vol_percent) = (channel.app_name.clone(), channel.vol_percent);
+
+    audio::submit(move |audio_system| {
+        for uid in uids {
+            // Stop right at the limit instead of stepping past it, grouped streams each at their own
+            let current = audio_system
+                .get_volume(uid, is_device, is_input)
+                .unwrap_or(vol_percent);
+            if current >= max_percent {
+                continue;
+            }
+            let step = step.min((max_percent - current) as f64 / 100.0);
+
+            if let Err(e) = audio_system.increase_volume(uid, step, is_device, is_input) {
+                println!("Warning: Failed to increase volume for {}: {}", app_name, e);
+                return;
+            }
+        }
+        println!("Volume up in app {} {}", app_name, vol_percent);
+    });
+}
+
+/// The device to make the default: the one named `device_name`, or without a name the one
+/// after the current default, wrapping around
+fn default_device_to_switch_to<'a>(
+    devices: &'a [AudioDevice],
+    device_name: Option<&str>,
+) -> Option<&'a AudioDevice> {
+    match device_name {
+        Some(device_name) => devices.iter().find(|device| device.name == device_name),
+        None => {
+            let current = devices.iter().position(|device| device.is_default);
+            devices.get(current.map_or(0, |position| (position + 1) % devices.len()))
+        }
+    }
+}
+
+/// The output device after `output_device` in `devices`, wrapping around. Streams with an
+/// unknown output are assumed to play on the default one. None if there is no other device.
+fn next_output_device<'a>(
+    devices: &'a [AudioDevice],
+    output_device: Option<&AudioDevice>,
+) -> Option<&'a AudioDevice> {
+    let current = devices.iter().position(|device| match output_device {
+        Some(output_device) => device.index == output_device.index,
+        None => device.is_default,
+    });
+    let next = current.map_or(0, |position| (position + 1) % devices.len());
+
+    devices.get(next).filter(|_| current != Some(next))
+}
+
+/// Whether a press of the single volume key of the compact layout lowers the volume.
+/// Short presses raise it, long ones lower it.
+fn compact_press_lowers_volume(duration_ms: u128) -> bool {
+    duration_ms >= COMPACT_VOLUME_DOWN_PRESS_MS
+}
+
+/// Step the volume of the channel on a column up for its upper and down for its lower volume key
+async fn step_volume_key(sd_column: u8, row: u8) {
+    let column_map = COLUMN_TO_CHANNEL_MAP.lock().await;
+    let channels = mixer::MIXER_CHANNELS.lock().await;
+
+    let Some(channel) = utils::get_channel_index(&column_map, sd_column)
+        .and_then(|channel_index| channels.get(&channel_index))
+    else {
+        return;
+    };
+
+    if row == 1 {
+        increase_volume(channel, VOLUME_INCREMENT);
+    } else {
+        decrease_volume(channel, VOLUME_INCREMENT);
+    }
+}
+
+/// Snap a channel to the configured default volume, capped at the current volume limit
+fn reset_volume(channel: &mixer::MixerChannel) {
+    if channel.is_placeholder {
+        return;
+    }
+
+    let target = (utils::get_default_volume() as f32).min(utils::get_max_volume_percent());
+
+    let (uids, is_device, is_input) = (channel.stream_uids(), channel.is_device, channel.is_input);
+    let (app_name, vol_percent) = (channel.app_name.clone(), channel.vol_percent);
+
+    audio::submit(move |audio_system| {
+        for uid in uids {
+            let current = audio_system
+                .get_volume(uid, is_device, is_input)
+                .unwrap_or(vol_percent);
+            if (current - target).abs() < 0.5 {
+                continue;
+            }
+
+            if let Err(e) = audio_system.set_volume(uid, target, is_device, is_input) {
+                println!("Warning: Failed to reset volume for {}: {}", app_name, e);
+                return;
+            }
+            println!("Volume of app {} reset from {} to {}", app_name, current, target);
+        }
+    });
+}
+
+/// Move a playback channel to the next output device, wrapping around
+fn cycle_output_device(channel: &mixer::MixerChannel) {
+    if channel.is_placeholder || channel.is_device || channel.is_input {
+        return;
+    }
+
+    let (uids, app_name) = (channel.stream_uids(), channel.app_name.clone());
+    let output_device = channel.output_device.clone();
+
+    audio::submit(move |audio_system| {
+        let devices = match audio_system.list_output_devices() {
+            Ok(devices) => devices,
+            Err(e) => {
+                println!("Warning: Failed to list output devices: {}", e);
+                return;
+            }
+        };
+
+        let Some(device) = next_output_device(&devices, output_device.as_ref()) else {
+            return;
+        };
+
+        // Grouped streams all follow the device shown on the channel
+        for uid in uids {
+            if let Err(e) = audio_system.move_to_sink(uid, device.index) {
+                println!("Warning: Failed to move {} to {}: {}", app_name, device.description, e);
+                return;
+            }
+        }
+        println!("Moved app {} to {}", app_name, device.description);
+    });
+}
+
+fn decrease_volume(channel: &mixer::MixerChannel, step: f64) {
+    if channel.is_placeholder {
+        return;
+    }
+
+    let (uids, is_device, is_input) = (channel.stream_uids(), channel.is_device, channel.is_input);
+    let (app_name, vol_percent) = (channel.app_name.clone(), channel.vol_percent);
+
+    audio::submit(move |audio_system| {
+        for uid in uids {
+            if let Err(e) = audio_system.decrease_volume(uid, step, is_device, is_input) {
+                println!("Warning: Failed to decrease volume for {}: {}", app_name, e);
+                return;
+            }
+        }
+        println!("Volume down in app {} {}", app_name, vol_percent);
+    });
+}
+
+/// Translate the pinned SD columns from the shared settings into mixer channel slots.
+/// Columns map to their position on the first page, so pinned apps always live there.
+pub async fn get_pinned_slots() -> HashMap<u8, String> {
+    let pinned_apps = SHARED_SETTINGS.lock().await.pinned_apps.clone();
+    let column_map = COLUMN_TO_CHANNEL_MAP.lock().await;
+
+    pinned_apps
+        .into_iter()
+        .filter_map(|(column, app_name)| column_map.get(&column).map(|&slot| (slot, app_name)))
+        .collect()
 }
 
 pub async fn init() -> OpenActionResult<()> {
-    println!("Stream Deck connected - starting PulseAudio monitoring");
+    let args: Vec<String> = std::env::args().collect();
+    let backend = audio::select_backend(&args);
+
+    println!("Stream Deck connected - starting {} monitoring", backend);
 
     // start listening to changes
-    audio::pulse::start_pulse_monitoring();
+    audio::start_monitoring();
 
     // create initial map (ignored apps will be loaded via did_receive_global_settings)
-    let applications = {
-        let mut audio_system = create();
-        audio_system
-            .list_applications()
-            .expect("Error fetching applications from SinkController")
+    let applications = audio::request(|audio_system| audio_system.list_applications())
+        .await
+        .unwrap_or_else(|e| {
+            eprintln!("Error fetching applications: {}", e);
+            Vec::new()
+        });
+
+    let filter = {
+        let shared = SHARED_SETTINGS.lock().await;
+        mixer::AppFilter::new(&shared.ignored_apps_list, &shared.app_filters, shared.filter_mode)
     };
-
-    let ignored_apps = SHARED_SETTINGS.lock().await.ignored_apps_list.clone();
-    mixer::create_mixer_channels(applications, &ignored_apps).await;
+    // Same pass as every refresh, so pins apply from the start
+    let pinned_apps = get_pinned_slots().await;
+    mixer::update_mixer_channels(applications, &filter, &pinned_apps).await;
 
     // Register global event handler and action
     set_global_event_handler(&GlobalHandler);
     register_action(VolumeControllerAction).await;
+    register_action(DefaultDeviceAction).await;
+    register_action(SceneAction).await;
+
+    run(args).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn two_row_devices_get_the_compact_layout() {
+        assert_eq!(ColumnLayout::for_rows(1), None);
+        assert_eq!(ColumnLayout::for_rows(2), Some(ColumnLayout::Compact));
+        assert_eq!(ColumnLayout::for_rows(3), Some(ColumnLayout::Full));
+        assert_eq!(ColumnLayout::for_rows(4), Some(ColumnLayout::Full));
+    }
+
+    #[test]
+    fn long_compact_presses_lower_the_volume() {
+        assert!(!compact_press_lowers_volume(50));
+        assert!(!compact_press_lowers_volume(COMPACT_VOLUME_DOWN_PRESS_MS - 1));
+        assert!(compact_press_lowers_volume(COMPACT_VOLUME_DOWN_PRESS_MS));
+        assert!(compact_press_lowers_volume(3000));
+    }
+
+    fn device(index: u32, is_default: bool) -> AudioDevice {
+        AudioDevice {
+            index,
+            name: format!("sink-{}", index),
+            description: format!("Sink {}", index),
+            icon_name: None,
+            is_default,
+        }
+    }
 
-    run(std::env::args().collect()).await
+    #[test]
+    fn output_devices_cycle_around() {
+        let devices = [device(1, false), device(2, true), device(3, false)];
+
+        let next = |current: Option<u32>| {
+            let current = current.map(|index| device(index, false));
+            next_output_device(&devices, current.as_ref()).map(|device| device.index)
+        };
+        assert_eq!(next(Some(1)), Some(2));
+        assert_eq!(next(Some(3)), Some(1));
+        // Unknown outputs play on the default device, unlisted ones start over
+        assert_eq!(next(None), Some(3));
+        assert_eq!(next(Some(7)), Some(1));
+    }
+
+    #[test]
+    fn single_output_device_has_nothing_to_cycle_to() {
+        let devices = [device(1, true)];
+        assert_eq!(next_output_device(&devices, Some(&devices[0])), None);
+        assert_eq!(next_output_device(&[], None), None);
+    }
+
+    #[test]
+    fn default_device_is_the_configured_or_the_next_one() {
+        let devices = [device(1, false), device(2, true), device(3, false)];
+        let switch_to = |device_name: Option<&str>| {
+            default_device_to_switch_to(&devices, device_name).map(|device| device.index)
+        };
+
+        assert_eq!(switch_to(Some("sink-1")), Some(1));
+        assert_eq!(switch_to(Some("sink-9")), None);
+        assert_eq!(switch_to(None), Some(3));
+
+        // Without a default it starts at the first device, the last one wraps around to it
+        let devices = [device(1, false), device(2, false)];
+        assert_eq!(default_device_to_switch_to(&devices, None).map(|device| device.index), Some(1));
+        let devices = [device(1, false), device(2, true)];
+        assert_eq!(default_device_to_switch_to(&devices, None).map(|device| device.index), Some(1));
+        assert_eq!(default_device_to_switch_to(&[], None), None);
+    }
 }
@@ -0,0 +1,269 @@
+use crate::audio::{self, AppInfo};
+use crate::ducking;
+use crate::plugin::{GlobalPluginSettings, SHARED_SETTINGS};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{LazyLock, Mutex};
+use std::time::Duration;
+
+/// Wait for volume changes to settle (e.g. a dial being turned) before saving them
+const SAVE_DELAY: Duration = Duration::from_secs(2);
+
+/// Volume and mute of an app, as last set while it was running
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct SavedVolume {
+    pub vol_percent: f32,
+    pub mute: bool,
+}
+
+// Last volume and mute seen of every playback stream, to tell changes apart from new streams
+static STREAM_STATES: LazyLock<Mutex<HashMap<u32, SavedVolume>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+// Set while a save of the remembered volumes is scheduled
+static SAVE_PENDING: AtomicBool = AtomicBool::new(false);
+
+/// Remember the volume and mute of apps whose playback streams changed. A new stream
+/// starts out where the sound server (or `restore_volumes`) put it, which only counts
+/// for apps that have nothing remembered yet. Ducked streams are left alone until their
+/// call ended, their volume then is their own again. When `all_streams` is set the list
+/// is complete and streams missing from it are forgotten.
+pub async fn remember_volumes(applications: &[AppInfo], all_streams: bool) {
+    let streams: Vec<&AppInfo> = applications
+        .iter()
+        .filter(|app| !app.is_device && !app.is_input && !ducking::is_ducked(app.uid))
+        .collect();
+
+    let changed = {
+        let mut shared = SHARED_SETTINGS.lock().await;
+        let mut states = STREAM_STATES.lock().unwrap();
+        if all_streams {
+            states.retain(|uid, _| {
+                applications
+                    .iter()
+                    .any(|app| app.uid == *uid && !app.is_device && !app.is_input)
+            });
+        }
+        remember_changes(&mut states, &mut shared.app_volumes, &streams)
+    };
+
+    if changed {
+        schedule_save();
+    }
+}
+
+/// Put the volume and mute of the streams that changed since `states` last saw them into
+/// `app_volumes`, returns whether anything remembered changed
+fn remember_changes(
+    states: &mut HashMap<u32, SavedVolume>,
+    app_volumes: &mut HashMap<String, SavedVolume>,
+    streams: &[&AppInfo],
+) -> bool {
+    let mut changed = false;
+
+    for app in streams {
+        let state = SavedVolume {
+            vol_percent: app.vol_percent,
+            mute: app.mute,
+        };
+        let is_new = match states.insert(app.uid, state) {
+            Some(previous) if previous == state => continue,
+            previous => previous.is_none(),
+        };
+        if is_new && app_volumes.contains_key(&app.app_id) {
+            continue;
+        }
+        changed |= app_volumes.insert(app.app_id.clone(), state) != Some(state);
+    }
+
+    changed
+}
+
+/// Forget the streams seen so far, e.g. after the sound server restarted and reuses stream indices
+pub fn forget_streams() {
+    STREAM_STATES.lock().unwrap().clear();
+}
+
+/// Give new playback streams of known apps the volume and mute their app had last time,
+/// unless the app opted out
+pub async fn restore_volumes(indices: Vec<u32>) {
+    let (app_volumes, opted_out) = {
+        let shared = SHARED_SETTINGS.lock().await;
+        (shared.app_volumes.clone(), shared.restore_opt_out.clone())
+    };
+    if app_volumes.is_empty() {
+        return;
+    }
+
+    for index in indices {
+        let app =
+            audio::request(move |audio_system| audio_system.get_application(index, false, false))
+                .await;
+        let Ok(Some(app)) = app else {
+            continue;
+        };
+        let Some(saved) = volume_to_restore(&app, &app_volumes, &opted_out) else {
+            continue;
+        };
+
+        let result = audio::request(move |audio_system| {
+            audio_system.set_volume(index, saved.vol_percent, false, false)?;
+            audio_system.mute_volume(index, saved.mute, false, false)
+        })
+        .await;
+
+        match result {
+            Ok(()) => println!(
+                "Restored {} to {}%{}",
+                app.app_id,
+                saved.vol_percent.round(),
+                if saved.mute { " (muted)" } else { "" }
+            ),
+            Err(e) => println!(
+                "Warning: Failed to restore the volume of {}: {}",
+                app.app_id, e
+            ),
+        }
+    }
+}
+
+/// The remembered volume and mute a new stream should get, if any and if not already set
+fn volume_to_restore(
+    app: &AppInfo,
+    app_volumes: &HashMap<String, SavedVolume>,
+    opted_out: &[String],
+) -> Option<SavedVolume> {
+    if opted_out.iter().any(|name| app.is_named(name)) {
+        return None;
+    }
+
+    let saved = *app_volumes.get(&app.app_id)?;
+    if saved.mute == app.mute && (saved.vol_percent - app.vol_percent).abs() < 0.5 {
+        return None;
+    }
+    Some(saved)
+}
+
+/// Save the remembered volumes to the global settings once changes settled
+fn schedule_save() {
+    debounce_save(&SAVE_PENDING, async {
+        let global = GlobalPluginSettings::from(&*SHARED_SETTINGS.lock().await);
+        if let Err(e) = openaction::set_global_settings(global).await {
+            eprintln!("Failed to save remembered volumes: {:?}", e);
+        }
+    });
+}
+
+/// Run `save` `SAVE_DELAY` after the first of several calls, which it covers all of.
+/// `pending` is set while a save is scheduled.
+fn debounce_save(pending: &'static AtomicBool, save: impl Future<Output = ()> + Send + 'static) {
+    if pending.swap(true, Ordering::AcqRel) {
+        return; // Already scheduled
+    }
+
+    let deadline = tokio::time::Instant::now() + SAVE_DELAY;
+    tokio::spawn(async move {
+        tokio::time::sleep_until(deadline).await;
+        pending.store(false, Ordering::Release);
+        save.await;
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::sync::atomic::AtomicUsize;
+
+    fn stream(uid: u32, app_id: &str, vol_percent: f32) -> AppInfo {
+        AppInfo {
+            vol_percent,
+            ..AppInfo::test_stream(uid, app_id)
+        }
+    }
+
+    fn saved(vol_percent: f32) -> SavedVolume {
+        SavedVolume {
+            vol_percent,
+            mute: false,
+        }
+    }
+
+    #[test]
+    fn known_apps_get_their_volume_back_unless_opted_out() {
+        let app_volumes = HashMap::from([("spotify".to_string(), saved(40.0))]);
+
+        let spotify = stream(1, "spotify", 100.0);
+        assert_eq!(
+            volume_to_restore(&spotify, &app_volumes, &[]),
+            Some(saved(40.0))
+        );
+        assert_eq!(
+            volume_to_restore(&spotify, &app_volumes, &["spotify".to_string()]),
+            None
+        );
+
+        // Nothing remembered, or already where it was
+        assert_eq!(
+            volume_to_restore(&stream(2, "vlc", 100.0), &app_volumes, &[]),
+            None
+        );
+        assert_eq!(
+            volume_to_restore(&stream(3, "spotify", 40.2), &app_volumes, &[]),
+            None
+        );
+    }
+
+    #[test]
+    fn volume_changes_of_streams_are_remembered() {
+        let mut states = HashMap::new();
+        let mut app_volumes = HashMap::new();
+
+        let first = stream(1, "spotify", 70.0);
+        assert!(remember_changes(&mut states, &mut app_volumes, &[&first]));
+        assert_eq!(app_volumes.get("spotify"), Some(&saved(70.0)));
+        assert!(!remember_changes(&mut states, &mut app_volumes, &[&first]));
+
+        // A change of the stream is remembered, a new stream starting elsewhere is not
+        let changed = stream(1, "spotify", 30.0);
+        let second = stream(2, "spotify", 100.0);
+        assert!(remember_changes(&mut states, &mut app_volumes, &[&changed]));
+        assert!(!remember_changes(&mut states, &mut app_volumes, &[&second]));
+        assert_eq!(app_volumes.get("spotify"), Some(&saved(30.0)));
+
+        // Until it is changed itself
+        let second = stream(2, "spotify", 90.0);
+        assert!(remember_changes(&mut states, &mut app_volumes, &[&second]));
+        assert_eq!(app_volumes.get("spotify"), Some(&saved(90.0)));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn saves_wait_for_changes_to_settle() {
+        static PENDING: AtomicBool = AtomicBool::new(false);
+        let saves = Arc::new(AtomicUsize::new(0));
+        let save = || {
+            let saves = saves.clone();
+            async move {
+                saves.fetch_add(1, Ordering::AcqRel);
+            }
+        };
+
+        debounce_save(&PENDING, save());
+        tokio::time::advance(SAVE_DELAY / 2).await;
+        debounce_save(&PENDING, save());
+        assert!(PENDING.load(Ordering::Acquire));
+        assert_eq!(saves.load(Ordering::Acquire), 0);
+
+        // Both changes go out in one save
+        tokio::time::advance(SAVE_DELAY).await;
+        tokio::task::yield_now().await;
+        assert_eq!(saves.load(Ordering::Acquire), 1);
+        assert!(!PENDING.load(Ordering::Acquire));
+
+        debounce_save(&PENDING, save());
+        tokio::time::advance(SAVE_DELAY).await;
+        tokio::task::yield_now().await;
+        assert_eq!(saves.load(Ordering::Acquire), 2);
+    }
+}
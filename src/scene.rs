@@ -0,0 +1,132 @@
+use crate::audio::{self, AppInfo};
+use crate::mixer::MIXER_CHANNELS;
+use crate::plugin::{GlobalPluginSettings, SHARED_SETTINGS};
+use serde::{Deserialize, Serialize};
+
+/// Volume and mute of one app or device in a scene
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SceneEntry {
+    /// Identity of the app, or the name of the device
+    pub app_id: String,
+    #[serde(default)]
+    pub is_device: bool,
+    #[serde(default)]
+    pub is_input: bool,
+    pub vol_percent: f32,
+    pub mute: bool,
+}
+
+impl SceneEntry {
+    fn matches(&self, app: &AppInfo) -> bool {
+        self.app_id == app.app_id
+            && self.is_device == app.is_device
+            && self.is_input == app.is_input
+    }
+}
+
+/// Store the volume and mute of every channel on the mixer, and of the default devices
+/// even while the system mixer is hidden, as the scene `name`. Apps lowered for a call
+/// are left out, the volume they have is not their own. Returns the number of entries.
+pub async fn capture_scene(name: &str) -> Result<usize, String> {
+    let applications = audio::request(|audio_system| audio_system.list_applications()).await?;
+
+    let mut entries: Vec<SceneEntry> = Vec::new();
+    let mut add_entry = |entry: SceneEntry| {
+        // Ungrouped apps can have several channels, the first one speaks for the app
+        let is_known = entries.iter().any(|known| {
+            known.app_id == entry.app_id
+                && known.is_device == entry.is_device
+                && known.is_input == entry.is_input
+        });
+        if !is_known {
+            entries.push(entry);
+        }
+    };
+
+    {
+        let channels = MIXER_CHANNELS.lock().await;
+        let mut slots: Vec<&u8> = channels.keys().collect();
+        slots.sort_unstable();
+
+        for channel in slots.into_iter().filter_map(|slot| channels.get(slot)) {
+            if channel.is_placeholder || channel.is_ducked {
+                continue;
+            }
+            add_entry(SceneEntry {
+                app_id: channel.app_id.clone(),
+                is_device: channel.is_device,
+                is_input: channel.is_input,
+                vol_percent: channel.vol_percent,
+                mute: channel.mute,
+            });
+        }
+    }
+
+    for device in applications.iter().filter(|app| app.is_device) {
+        add_entry(SceneEntry {
+            app_id: device.app_id.clone(),
+            is_device: true,
+            is_input: device.is_input,
+            vol_percent: device.vol_percent,
+            mute: device.mute,
+        });
+    }
+
+    let count = entries.len();
+    let global = {
+        let mut shared = SHARED_SETTINGS.lock().await;
+        shared.scenes.insert(name.to_string(), entries);
+        GlobalPluginSettings::from(&*shared)
+    };
+
+    openaction::set_global_settings(global)
+        .await
+        .map_err(|e| format!("{:?}", e))?;
+
+    Ok(count)
+}
+
+/// Give every stream and device covered by the scene `name` the volume and mute it had
+/// when the scene was captured. Apps the scene doesn't know are left alone.
+/// Returns the number of streams and devices the scene covered.
+pub async fn recall_scene(name: &str) -> Result<usize, String> {
+    let Some(entries) = SHARED_SETTINGS.lock().await.scenes.get(name).cloned() else {
+        return Err(format!("No scene named {}", name));
+    };
+
+    audio::request(move |audio_system| {
+        let mut recalled = 0;
+
+        for app in audio_system.list_applications()? {
+            let Some(entry) = entries.iter().find(|entry| entry.matches(&app)) else {
+                continue;
+            };
+
+            if (entry.vol_percent - app.vol_percent).abs() >= 0.5 {
+                audio_system.set_volume(app.uid, entry.vol_percent, app.is_device, app.is_input)?;
+            }
+            if entry.mute != app.mute {
+                audio_system.mute_volume(app.uid, entry.mute, app.is_device, app.is_input)?;
+            }
+            recalled += 1;
+        }
+
+        Ok(recalled)
+    })
+    .await
+}
+
+/// Forget the scene `name`
+pub async fn delete_scene(name: &str) -> Result<(), String> {
+    let global = {
+        let mut shared = SHARED_SETTINGS.lock().await;
+        if shared.scenes.remove(name).is_none() {
+            return Ok(());
+        }
+        GlobalPluginSettings::from(&*shared)
+    };
+
+    openaction::set_global_settings(global)
+        .await
+        .map_err(|e| format!("{:?}", e))
+}
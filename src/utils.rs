@@ -1,21 +1,114 @@
-use openaction::{Action, Instance, visible_instances};
+use openaction::{Action, Instance, get_connected_devices, visible_instances};
 use tux_icons::icon_fetcher::IconFetcher;
 
-use std::sync::atomic::{AtomicBool, Ordering};
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::collections::HashMap;
+use std::sync::LazyLock;
+use std::sync::atomic::{AtomicBool, AtomicU8, AtomicU16, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::Mutex;
 
-use crate::gfx::TRANSPARENT_ICON;
+use crate::audio::{MAX_BOOST_VOLUME_PERCENT, NORMAL_VOLUME_PERCENT};
+use crate::gfx::{self, PageDirection, TRANSPARENT_ICON};
 use crate::mixer::{self, MixerChannel};
-use crate::plugin::{COLUMN_TO_CHANNEL_MAP, VolumeControllerAction};
+use crate::plugin::{
+    COLUMN_TO_CHANNEL_MAP, DEFAULT_DEVICE_SETTINGS, DefaultDeviceAction, DefaultDeviceSettings,
+    SHARED_SETTINGS, SceneSettings, VolumeControllerAction,
+};
 
 const MAX_TITLE_CHARS_BEFORE_TRUNCATION: usize = 8;
+const ENCODER_CONTROLLER: &str = "Encoder";
+const DISCONNECTED_TITLE: &str = "Audio\noffline";
+const DUCKED_TITLE: &str = "Ducked";
+pub const DEFAULT_VOLUME_PERCENT: u8 = 100;
+pub const DEFAULT_LEVEL_METER_RATE: u8 = 10;
+const MAX_LEVEL_METER_RATE: u8 = 30;
+pub const DEFAULT_INACTIVE_TIMEOUT_SECS: u16 = 10;
+pub const DEFAULT_DUCKING_PERCENT: u8 = 50;
 
 // Global flag to track if system mixer should be shown
 static SHOW_SYSTEM_MIXER: AtomicBool = AtomicBool::new(false);
 
+// Global flag to track if microphones and recording apps should be shown
+static SHOW_INPUT_CHANNELS: AtomicBool = AtomicBool::new(false);
+
+// Global flag to track if volumes may be raised above 100%
+static ALLOW_VOLUME_BOOST: AtomicBool = AtomicBool::new(false);
+
+// Volume the "both volume keys" gesture snaps a channel to
+static DEFAULT_VOLUME: AtomicU8 = AtomicU8::new(DEFAULT_VOLUME_PERCENT);
+
+// Global flag to track if level meters should be drawn next to the volume bars
+static SHOW_LEVEL_METERS: AtomicBool = AtomicBool::new(false);
+
+// Level meter updates per second
+static LEVEL_METER_RATE: AtomicU8 = AtomicU8::new(DEFAULT_LEVEL_METER_RATE);
+
+// Global flag to track if paused and silent apps should be hidden
+static HIDE_INACTIVE_APPS: AtomicBool = AtomicBool::new(false);
+
+// Seconds an app has to be paused or silent before it is hidden
+static INACTIVE_TIMEOUT_SECS: AtomicU16 = AtomicU16::new(DEFAULT_INACTIVE_TIMEOUT_SECS);
+
+// Global flag to track if all streams of an app should share one channel
+static GROUP_APP_STREAMS: AtomicBool = AtomicBool::new(false);
+
+// Global flag to track if grouped channels show the average instead of the loudest volume
+static AVERAGE_GROUP_VOLUME: AtomicBool = AtomicBool::new(false);
+
+// Global flag to track if other apps should be lowered while a call plays
+static AUTO_DUCKING: AtomicBool = AtomicBool::new(false);
+
+// Percent of their volume other apps are lowered by during calls
+static DUCKING_PERCENT: AtomicU8 = AtomicU8::new(DEFAULT_DUCKING_PERCENT);
+
+// Icon of the output device key, looked up once
+static OUTPUT_DEVICE_ICON: LazyLock<String> = LazyLock::new(|| {
+    get_app_icon_uri(Some("audio-speakers".to_string()), "audio-card".to_string()).0
+});
+
+// Icon shown on every column while the sound server is unreachable, looked up once
+static DISCONNECTED_ICON: LazyLock<String> =
+    LazyLock::new(|| get_app_icon_uri(Some("audio-card".to_string()), "audio-card".to_string()).1);
+
+// Whether the sound server is reachable, cleared by the monitor while it reconnects
+static AUDIO_CONNECTED: AtomicBool = AtomicBool::new(true);
+
+// Image and title last sent to each key (or dial) by instance id
+static RENDER_CACHE: LazyLock<Mutex<HashMap<String, RenderState>>> =
+    LazyLock::new(|| Mutex::const_new(HashMap::new()));
+
+// Page of mixer channels currently shown on the deck
+static CURRENT_PAGE: AtomicU8 = AtomicU8::new(0);
+
+/// What a key currently shows, as far as the plugin knows
+#[derive(Default)]
+struct RenderState {
+    image: Option<String>,
+    title: Option<String>,
+}
+
+/// Part of what a key shows
+#[derive(Clone, Copy)]
+enum RenderPart {
+    Image,
+    Title,
+}
+
+impl RenderState {
+    fn part(&mut self, part: RenderPart) -> &mut Option<String> {
+        match part {
+            RenderPart::Image => &mut self.image,
+            RenderPart::Title => &mut self.title,
+        }
+    }
+}
+
 pub struct ButtonPressControl {
     pub action_id: Option<String>,
     time_ms: Option<u128>,
+    /// (column, row) of the volume keys currently held down, and whether each of them
+    /// was held together with the other volume key of its column
+    held_volume_keys: HashMap<(u8, u8), bool>,
 }
 
 impl ButtonPressControl {
@@ -23,9 +116,28 @@ impl ButtonPressControl {
         ButtonPressControl {
             action_id: None,
             time_ms: None,
+            held_volume_keys: HashMap::new(),
         }
     }
 
+    /// Track a volume key going down, returns true if the other volume key
+    /// of the same column is already held
+    pub fn press_volume_key(&mut self, column: u8, row: u8) -> bool {
+        let other_key = (column, if row == 1 { 2 } else { 1 });
+        let both = self.held_volume_keys.contains_key(&other_key);
+        if both {
+            self.held_volume_keys.insert(other_key, true);
+        }
+        self.held_volume_keys.insert((column, row), both);
+        both
+    }
+
+    /// Track a volume key going up, returns true if it was held on its own
+    /// and not together with the other volume key of its column
+    pub fn release_volume_key(&mut self, column: u8, row: u8) -> bool {
+        self.held_volume_keys.remove(&(column, row)) == Some(false)
+    }
+
     pub fn set_press_time(&mut self, action_id: String) {
         self.action_id = Some(action_id);
         self.time_ms = Some(
@@ -62,7 +174,249 @@ pub fn set_show_system_mixer(value: bool) {
     SHOW_SYSTEM_MIXER.store(value, Ordering::Relaxed);
 }
 
-pub async fn get_device_row_count() -> Option<u8> {
+pub fn should_show_input_channels() -> bool {
+    SHOW_INPUT_CHANNELS.load(Ordering::Relaxed)
+}
+
+pub fn set_show_input_channels(value: bool) {
+    SHOW_INPUT_CHANNELS.store(value, Ordering::Relaxed);
+}
+
+pub fn is_volume_boost_allowed() -> bool {
+    ALLOW_VOLUME_BOOST.load(Ordering::Relaxed)
+}
+
+pub fn set_allow_volume_boost(value: bool) {
+    ALLOW_VOLUME_BOOST.store(value, Ordering::Relaxed);
+}
+
+/// Highest volume the deck may raise a channel to
+pub fn get_max_volume_percent() -> f32 {
+    if is_volume_boost_allowed() {
+        MAX_BOOST_VOLUME_PERCENT
+    } else {
+        NORMAL_VOLUME_PERCENT
+    }
+}
+
+/// Volume at the top of a channel's volume bar. Channels boosted elsewhere
+/// (e.g. in pavucontrol) get the boost scale too so their true volume shows.
+pub fn get_volume_bar_max_percent(vol_percent: f32) -> f32 {
+    if vol_percent > NORMAL_VOLUME_PERCENT {
+        MAX_BOOST_VOLUME_PERCENT.max(vol_percent)
+    } else {
+        get_max_volume_percent()
+    }
+}
+
+pub fn get_default_volume() -> u8 {
+    DEFAULT_VOLUME.load(Ordering::Relaxed)
+}
+
+pub fn set_default_volume(value: u8) {
+    DEFAULT_VOLUME.store(value, Ordering::Relaxed);
+}
+
+pub fn should_show_level_meters() -> bool {
+    SHOW_LEVEL_METERS.load(Ordering::Relaxed)
+}
+
+pub fn set_show_level_meters(value: bool) {
+    SHOW_LEVEL_METERS.store(value, Ordering::Relaxed);
+}
+
+pub fn get_level_meter_rate() -> u8 {
+    LEVEL_METER_RATE.load(Ordering::Relaxed)
+}
+
+pub fn set_level_meter_rate(value: u8) {
+    LEVEL_METER_RATE.store(value.clamp(1, MAX_LEVEL_METER_RATE), Ordering::Relaxed);
+}
+
+pub fn should_hide_inactive_apps() -> bool {
+    HIDE_INACTIVE_APPS.load(Ordering::Relaxed)
+}
+
+pub fn set_hide_inactive_apps(value: bool) {
+    HIDE_INACTIVE_APPS.store(value, Ordering::Relaxed);
+}
+
+pub fn get_inactive_timeout() -> Duration {
+    Duration::from_secs(INACTIVE_TIMEOUT_SECS.load(Ordering::Relaxed) as u64)
+}
+
+pub fn set_inactive_timeout(seconds: u16) {
+    INACTIVE_TIMEOUT_SECS.store(seconds.max(1), Ordering::Relaxed);
+}
+
+pub fn should_group_app_streams() -> bool {
+    GROUP_APP_STREAMS.load(Ordering::Relaxed)
+}
+
+pub fn set_group_app_streams(value: bool) {
+    GROUP_APP_STREAMS.store(value, Ordering::Relaxed);
+}
+
+pub fn should_average_group_volume() -> bool {
+    AVERAGE_GROUP_VOLUME.load(Ordering::Relaxed)
+}
+
+pub fn set_average_group_volume(value: bool) {
+    AVERAGE_GROUP_VOLUME.store(value, Ordering::Relaxed);
+}
+
+pub fn should_auto_duck() -> bool {
+    AUTO_DUCKING.load(Ordering::Relaxed)
+}
+
+pub fn set_auto_duck(value: bool) {
+    AUTO_DUCKING.store(value, Ordering::Relaxed);
+}
+
+pub fn get_ducking_percent() -> u8 {
+    DUCKING_PERCENT.load(Ordering::Relaxed)
+}
+
+pub fn set_ducking_percent(value: u8) {
+    DUCKING_PERCENT.store(value.clamp(1, 100), Ordering::Relaxed);
+}
+
+pub fn is_audio_connected() -> bool {
+    AUDIO_CONNECTED.load(Ordering::Relaxed)
+}
+
+pub fn set_audio_connected(value: bool) {
+    AUDIO_CONNECTED.store(value, Ordering::Relaxed);
+}
+
+pub fn get_current_page() -> u8 {
+    CURRENT_PAGE.load(Ordering::Relaxed)
+}
+
+pub fn set_current_page(page: u8) {
+    CURRENT_PAGE.store(page, Ordering::Relaxed);
+}
+
+/// Number of channels shown per page, one for every column position in the column map
+pub fn get_page_size(column_map: &HashMap<u8, u8>) -> u8 {
+    column_map
+        .values()
+        .max()
+        .map_or(0, |&position| position.saturating_add(1))
+}
+
+/// Number of pages needed for every channel, there can be one more than `u8` holds
+pub fn get_page_count(column_map: &HashMap<u8, u8>, channels: &HashMap<u8, MixerChannel>) -> u16 {
+    let page_size = get_page_size(column_map);
+    if page_size == 0 {
+        return 1;
+    }
+
+    channels
+        .keys()
+        .max()
+        .map_or(1, |&max_slot| (max_slot / page_size) as u16 + 1)
+}
+
+/// Channel slot shown on the given SD column for the current page
+pub fn get_channel_index(column_map: &HashMap<u8, u8>, sd_column: u8) -> Option<u8> {
+    let position = *column_map.get(&sd_column)?;
+    let page_size = get_page_size(column_map);
+
+    Some(
+        get_current_page()
+            .saturating_mul(page_size)
+            .saturating_add(position),
+    )
+}
+
+/// Move to the previous or next page of channels, wrapping around at both ends
+pub async fn turn_page(direction: PageDirection) {
+    {
+        let column_map = COLUMN_TO_CHANNEL_MAP.lock().await;
+        let channels = mixer::MIXER_CHANNELS.lock().await;
+
+        let page_count = get_page_count(&column_map, &channels);
+        let page = (get_current_page() as u16).min(page_count - 1);
+
+        let new_page = match direction {
+            PageDirection::Previous => page.checked_sub(1).unwrap_or(page_count - 1),
+            PageDirection::Next => (page + 1) % page_count,
+        };
+        // Every page starts at a slot, so the last one still fits a `u8`
+        set_current_page(new_page as u8);
+
+        println!("Showing page {} of {}", new_page + 1, page_count);
+    }
+
+    update_stream_deck_buttons().await;
+}
+
+// Dials (and their touch strip segments) on the Stream Deck+ report the "Encoder" controller
+pub fn is_dial(instance: &Instance) -> bool {
+    instance.controller == ENCODER_CONTROLLER
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ColumnLayout {
+    /// Header, volume up and volume down keys stacked on 3 rows, plus an output
+    /// device key on the 4th row of devices that have one
+    Full,
+    /// Header and a single volume key for 2 row devices (Mini, Neo, SD+ keypad)
+    Compact,
+}
+
+/// Row counts of the connected devices, looked up once per redraw pass
+/// instead of once per key
+pub struct DeviceRows {
+    rows: HashMap<String, u8>,
+    /// Rows actually covered by the action, for devices that don't report their size
+    fallback: Option<u8>,
+}
+
+impl DeviceRows {
+    pub async fn lookup() -> Self {
+        let rows: HashMap<String, u8> = get_connected_devices()
+            .await
+            .into_iter()
+            .map(|(device_id, device)| (device_id, device.size.rows))
+            .collect();
+
+        DeviceRows {
+            rows,
+            fallback: get_device_row_count().await,
+        }
+    }
+
+    /// Pick the column layout from the row count of the device this instance lives on,
+    /// falling back to the rows actually covered by the action
+    pub fn column_layout(&self, instance: &Instance) -> Option<ColumnLayout> {
+        let rows = match self.rows.get(&instance.device_id) {
+            Some(&rows) => rows,
+            None => self.fallback?,
+        };
+
+        ColumnLayout::for_rows(rows)
+    }
+}
+
+impl ColumnLayout {
+    /// Layout for a device with `rows` rows of keys, devices with a single row get none
+    pub fn for_rows(rows: u8) -> Option<ColumnLayout> {
+        match rows {
+            0 | 1 => None,
+            2 => Some(ColumnLayout::Compact),
+            _ => Some(ColumnLayout::Full),
+        }
+    }
+}
+
+/// Column layout of a single instance, e.g. for a key press
+pub async fn get_column_layout(instance: &Instance) -> Option<ColumnLayout> {
+    DeviceRows::lookup().await.column_layout(instance)
+}
+
+async fn get_device_row_count() -> Option<u8> {
     let instances = visible_instances(VolumeControllerAction::UUID).await;
     if instances.is_empty() {
         return None;
@@ -70,6 +424,7 @@ pub async fn get_device_row_count() -> Option<u8> {
 
     let max_row = instances
         .iter()
+        .filter(|i| !is_dial(i))
         .filter_map(|i| i.coordinates.as_ref())
         .map(|coords| coords.row)
         .max()?;
@@ -78,9 +433,23 @@ pub async fn get_device_row_count() -> Option<u8> {
 }
 
 pub async fn update_stream_deck_buttons() {
+    if !is_audio_connected() {
+        show_disconnected().await;
+        return;
+    }
+
+    let paging_column = SHARED_SETTINGS.lock().await.paging_column;
+
     let column_map = COLUMN_TO_CHANNEL_MAP.lock().await;
     let mut channels = mixer::MIXER_CHANNELS.lock().await;
-    let row_count = get_device_row_count().await;
+
+    // Channels may have disappeared since the page was selected
+    let page_count = get_page_count(&column_map, &channels);
+    if get_current_page() as u16 >= page_count {
+        set_current_page((page_count - 1) as u8);
+    }
+
+    let device_rows = DeviceRows::lookup().await;
 
     for instance in visible_instances(VolumeControllerAction::UUID).await {
         let Some(coords) = instance.coordinates else {
@@ -88,74 +457,355 @@ pub async fn update_stream_deck_buttons() {
         };
         let sd_column = coords.column;
 
-        let Some(&channel_index) = column_map.get(&sd_column) else {
+        let layout = device_rows.column_layout(&instance);
+
+        if paging_column == Some(sd_column) {
+            update_paging_key(&instance, layout, page_count).await;
+            continue;
+        }
+
+        let Some(channel_index) = get_channel_index(&column_map, sd_column) else {
             continue;
         };
 
         let Some(channel) = channels.get_mut(&channel_index) else {
-            if let Some(rows) = row_count {
-                if rows >= 3 {
-                    cleanup_sd_column(&instance).await;
-                } else {
-                    // TODO check if there are knobs/dials too and call appropriate cleanup fn
-                    // update_sd_column_with_knob(&instance).await;
-                }
+            if is_dial(&instance) || layout.is_some() {
+                cleanup_sd_column(&instance).await;
             }
             continue;
         };
 
+        if is_dial(&instance) {
+            channel.dial_id = Some(instance.instance_id.clone());
+            update_sd_dial(channel, &instance).await;
+            continue;
+        }
+
         match coords.row {
             0 => channel.header_id = Some(instance.instance_id.clone()),
             1 => channel.upper_vol_btn_id = Some(instance.instance_id.clone()),
             2 => channel.lower_vol_btn_id = Some(instance.instance_id.clone()),
+            3 => channel.output_btn_id = Some(instance.instance_id.clone()),
             _ => {}
         }
 
-        if let Some(rows) = row_count {
-            if rows >= 3 {
-                update_sd_column(channel, &instance).await;
-            } else {
-                // TODO same logic as in cleanup for knobs/dials (appropriate update fn)
-                // update_sd_column_with_knob(&instance).await;
-            }
+        if let Some(layout) = layout {
+            update_sd_column(channel, &instance, layout).await;
         }
     }
 }
 
+/// Redraw only the keys (and dials) currently showing the channels in `slots`
+pub async fn update_channel_keys(slots: &[u8]) {
+    if slots.is_empty() {
+        return;
+    }
+
+    let paging_column = SHARED_SETTINGS.lock().await.paging_column;
+
+    let column_map = COLUMN_TO_CHANNEL_MAP.lock().await;
+    let channels = mixer::MIXER_CHANNELS.lock().await;
+    let device_rows = DeviceRows::lookup().await;
+
+    for instance in visible_instances(VolumeControllerAction::UUID).await {
+        let Some(coords) = instance.coordinates else {
+            continue;
+        };
+
+        if paging_column == Some(coords.column) {
+            continue;
+        }
+        let Some(channel) = get_channel_index(&column_map, coords.column)
+            .filter(|slot| slots.contains(slot))
+            .and_then(|slot| channels.get(&slot))
+        else {
+            continue;
+        };
+
+        if is_dial(&instance) {
+            update_sd_dial(channel, &instance).await;
+        } else if let Some(layout) = device_rows.column_layout(&instance) {
+            update_sd_column(channel, &instance, layout).await;
+        }
+    }
+}
+
+/// Grey out every column while the sound server is gone
+async fn show_disconnected() {
+    for instance in visible_instances(VolumeControllerAction::UUID).await {
+        show_disconnected_key(&instance).await;
+    }
+}
+
+/// The first row of a column (or its dial) tells the sound server is gone,
+/// the other keys are cleared
+async fn show_disconnected_key(instance: &Instance) {
+    cleanup_sd_column(instance).await;
+
+    let is_first_row = instance.coordinates.is_some_and(|coords| coords.row == 0);
+    if is_first_row || is_dial(instance) {
+        set_key_image(instance, DISCONNECTED_ICON.as_str()).await;
+        set_key_title(instance, DISCONNECTED_TITLE).await;
+    }
+}
+
+/// Redraw every default device switcher, e.g. after the default device changed
+pub async fn update_default_device_keys() {
+    let settings = DEFAULT_DEVICE_SETTINGS.lock().await.clone();
+
+    for instance in visible_instances(DefaultDeviceAction::UUID).await {
+        if let Some(settings) = settings.get(&instance.instance_id) {
+            update_default_device_key(&instance, settings).await;
+        }
+    }
+}
+
+/// Render a default device switcher: the current default device, or the device it
+/// selects greyed out while that device is not the default one
+pub async fn update_default_device_key(instance: &Instance, settings: &DefaultDeviceSettings) {
+    if !is_audio_connected() {
+        set_key_image(instance, DISCONNECTED_ICON.as_str()).await;
+        set_key_title(instance, DISCONNECTED_TITLE).await;
+        return;
+    }
+
+    let is_input = settings.is_input;
+    let devices = crate::audio::request(move |audio_system| {
+        if is_input {
+            audio_system.list_input_devices()
+        } else {
+            audio_system.list_output_devices()
+        }
+    })
+    .await
+    .unwrap_or_default();
+
+    let device = match &settings.device_name {
+        Some(device_name) => devices.iter().find(|device| &device.name == device_name),
+        None => devices.iter().find(|device| device.is_default),
+    };
+
+    let Some(device) = device else {
+        cleanup_sd_column(instance).await;
+        set_key_title(instance, "No device").await;
+        return;
+    };
+
+    let fallback_icon_name = if settings.is_input {
+        "audio-input-microphone"
+    } else {
+        "audio-card"
+    };
+    let (icon_uri, icon_uri_mute, _) =
+        get_app_icon_uri(device.icon_name.clone(), fallback_icon_name.to_string());
+
+    let icon_uri = if device.is_default {
+        icon_uri
+    } else {
+        icon_uri_mute
+    };
+
+    set_key_image(instance, icon_uri).await;
+    set_key_title(instance, truncate_title(&device.description)).await;
+}
+
+/// Render a scene key: the name of the scene it recalls
+pub async fn update_scene_key(instance: &Instance, settings: &SceneSettings) {
+    let scene_name = settings.scene_name.trim();
+    if scene_name.is_empty() {
+        set_key_title(instance, "No scene").await;
+    } else {
+        set_key_title(instance, truncate_title(scene_name)).await;
+    }
+}
+
+/// Render a key (or dial) of the paging column: the page indicator on the first row
+/// (or the touch strip) and previous/next page arrows below it
+pub async fn update_paging_key(instance: &Instance, layout: Option<ColumnLayout>, page_count: u16) {
+    let Some(coords) = instance.coordinates else {
+        return;
+    };
+
+    let page = get_current_page();
+    let page_title = format!("{}/{}", page as u16 + 1, page_count);
+
+    if is_dial(instance) {
+        if let Ok(img) = gfx::get_touch_strip_page_indicator_data_uri(page, page_count) {
+            set_key_image(instance, img).await;
+        }
+        set_key_title(instance, format!("Page {}", page_title)).await;
+        return;
+    }
+
+    let (image, title) = match (coords.row, layout) {
+        (0, Some(_)) => (
+            gfx::get_page_indicator_data_uri(page, page_count),
+            page_title,
+        ),
+        (1, Some(_)) => (
+            gfx::get_page_arrow_data_uri(PageDirection::Previous),
+            String::new(),
+        ),
+        (2, Some(ColumnLayout::Full)) => (
+            gfx::get_page_arrow_data_uri(PageDirection::Next),
+            String::new(),
+        ),
+        _ => return,
+    };
+
+    if let Ok(img) = image {
+        set_key_image(instance, img).await;
+    }
+    set_key_title(instance, title).await;
+}
+
+/// Page direction for a key (or dial press) of the paging column
+pub fn get_paging_direction(instance: &Instance) -> Option<PageDirection> {
+    if is_dial(instance) {
+        return Some(PageDirection::Next);
+    }
+
+    match instance.coordinates?.row {
+        0 | 2 => Some(PageDirection::Next),
+        1 => Some(PageDirection::Previous),
+        _ => None,
+    }
+}
+
 pub async fn update_header(instance: &Instance, channel: &MixerChannel) {
+    if channel.is_placeholder {
+        set_key_image(instance, channel.icon_uri_mute.clone()).await;
+        set_key_title(instance, not_running_title(&channel.app_name)).await;
+        return;
+    }
+
+    if channel.is_hidden {
+        set_key_image(instance, channel.icon_uri_mute.clone()).await;
+        set_key_title(instance, idle_title(&channel.app_name)).await;
+        return;
+    }
+
     let icon_uri = if channel.mute {
         channel.icon_uri_mute.clone()
     } else {
         channel.icon_uri.clone()
     };
 
-    let _ = instance.set_image(Some(icon_uri), None).await;
+    set_key_image(instance, icon_uri).await;
 
     // Set title based on priority: multi-sink app > uses default icon > no title
-    if channel.is_multi_sink_app {
-        let _ = instance
-            .set_title(
-                channel.sink_name.as_ref().map(|name| {
-                    if name.len() > MAX_TITLE_CHARS_BEFORE_TRUNCATION {
-                        format!(
-                            "{}...",
-                            name.chars()
-                                .take(MAX_TITLE_CHARS_BEFORE_TRUNCATION)
-                                .collect::<String>()
-                        )
-                    } else {
-                        name.clone()
-                    }
-                }),
-                None,
-            )
-            .await;
+    let title = if channel.is_multi_sink_app {
+        channel.sink_name.as_ref().map(|name| truncate_title(name))
     } else if channel.uses_default_icon {
-        let _ = instance
-            .set_title(Some(channel.app_name.clone()), None)
-            .await;
+        Some(channel.app_name.clone())
     } else {
-        let _ = instance.set_title(Some(""), None).await;
+        Some(String::new())
+    };
+
+    set_key_title(
+        instance,
+        with_ducked_line(with_output_device_line(title, channel), channel).unwrap_or_default(),
+    )
+    .await;
+}
+
+/// Apps routed to an output other than the default one show it on an extra title line
+fn with_output_device_line(title: Option<String>, channel: &MixerChannel) -> Option<String> {
+    let Some(device) = channel
+        .output_device
+        .as_ref()
+        .filter(|device| !device.is_default)
+    else {
+        return title;
+    };
+
+    let device_line = truncate_title(&device.description);
+    match title {
+        Some(title) if !title.is_empty() => Some(format!("{}\n{}", title, device_line)),
+        _ => Some(device_line),
+    }
+}
+
+/// Channels lowered for a call say so on an extra title line
+fn with_ducked_line(title: Option<String>, channel: &MixerChannel) -> Option<String> {
+    if !channel.is_ducked {
+        return title;
+    }
+
+    match title {
+        Some(title) if !title.is_empty() => Some(format!("{}\n{}", title, DUCKED_TITLE)),
+        _ => Some(DUCKED_TITLE.to_string()),
+    }
+}
+
+/// Render the output device key of a playback channel: the current device as title,
+/// pressing it moves the app to the next output device
+pub async fn update_output_device_key(instance: &Instance, channel: &MixerChannel) {
+    let Some(device) = channel.output_device.as_ref() else {
+        cleanup_sd_column(instance).await;
+        return;
+    };
+
+    set_key_image(instance, OUTPUT_DEVICE_ICON.as_str()).await;
+    set_key_title(instance, truncate_title(&device.description)).await;
+}
+
+/// Render the touch strip segment above a dial: app icon, volume bar and app name
+pub async fn update_sd_dial(channel: &MixerChannel, instance: &Instance) {
+    if !is_audio_connected() {
+        show_disconnected_key(instance).await;
+        return;
+    }
+
+    let greyed_out = channel.mute || channel.is_placeholder || channel.is_hidden;
+    let icon_uri = if greyed_out {
+        &channel.icon_uri_mute
+    } else {
+        &channel.icon_uri
+    };
+
+    if let Ok(img) = crate::gfx::get_touch_strip_data_uri(
+        icon_uri,
+        channel.vol_percent,
+        get_volume_bar_max_percent(channel.vol_percent),
+        greyed_out,
+        channel.is_input,
+    ) {
+        set_key_image(instance, img).await;
+    }
+
+    let title = match (channel.is_multi_sink_app, channel.sink_name.as_ref()) {
+        _ if channel.is_placeholder => not_running_title(&channel.app_name),
+        _ if channel.is_hidden => idle_title(&channel.app_name),
+        (true, Some(sink_name)) => format!("{} ({})", channel.app_name, truncate_title(sink_name)),
+        _ => channel.app_name.clone(),
+    };
+    set_key_title(
+        instance,
+        with_ducked_line(with_output_device_line(Some(title), channel), channel)
+            .unwrap_or_default(),
+    )
+    .await;
+}
+
+fn not_running_title(app_name: &str) -> String {
+    format!("{}\nnot running", truncate_title(app_name))
+}
+
+fn idle_title(app_name: &str) -> String {
+    format!("{}\nidle", truncate_title(app_name))
+}
+
+fn truncate_title(name: &str) -> String {
+    if name.chars().count() > MAX_TITLE_CHARS_BEFORE_TRUNCATION {
+        format!(
+            "{}...",
+            name.chars()
+                .take(MAX_TITLE_CHARS_BEFORE_TRUNCATION)
+                .collect::<String>()
+        )
+    } else {
+        name.to_string()
     }
 }
 
@@ -229,11 +879,73 @@ pub fn get_app_icon_uri(
     (normal_uri, muted_uri, uses_default_icon)
 }
 
+/// Send `value` as a part of a key with `send`, unless the key already shows it.
+/// Only what was sent is remembered, so a failed send is tried again on the next render.
+/// Returns whether it was sent.
+async fn send_render_part<F, Fut, E>(
+    instance_id: &str,
+    part: RenderPart,
+    value: String,
+    send: F,
+) -> bool
+where
+    F: FnOnce(String) -> Fut,
+    Fut: Future<Output = Result<(), E>>,
+{
+    {
+        let mut cache = RENDER_CACHE.lock().await;
+        if let Some(state) = cache.get_mut(instance_id)
+            && state.part(part).as_ref() == Some(&value)
+        {
+            return false;
+        }
+    }
+
+    if send(value.clone()).await.is_err() {
+        return false;
+    }
+
+    let mut cache = RENDER_CACHE.lock().await;
+    *cache.entry(instance_id.to_string()).or_default().part(part) = Some(value);
+    true
+}
+
+/// Send an image to a key, unless the key already shows it
+async fn set_key_image(instance: &Instance, image: impl Into<String>) {
+    send_render_part(
+        &instance.instance_id,
+        RenderPart::Image,
+        image.into(),
+        |image| instance.set_image(Some(image), None),
+    )
+    .await;
+}
+
+/// Send a title to a key, unless the key already shows it
+async fn set_key_title(instance: &Instance, title: impl Into<String>) {
+    send_render_part(
+        &instance.instance_id,
+        RenderPart::Title,
+        title.into(),
+        |title| instance.set_title(Some(title), None),
+    )
+    .await;
+}
+
+/// Forget what a key shows, e.g. when it (re)appears on the deck after a profile switch
+/// and OpenDeck drew it from scratch. The next render sends everything again.
+pub async fn forget_render_state(instance_id: &str) {
+    RENDER_CACHE.lock().await.remove(instance_id);
+}
+
+/// Forget what every key shows, e.g. when a device reconnects
+pub async fn clear_render_cache() {
+    RENDER_CACHE.lock().await.clear();
+}
+
 pub async fn cleanup_sd_column(instance: &Instance) {
-    let _ = instance.set_title(Some(""), None).await;
-    let _ = instance
-        .set_image(Some(TRANSPARENT_ICON.as_str()), None)
-        .await;
+    set_key_title(instance, "").await;
+    set_key_image(instance, TRANSPARENT_ICON.as_str()).await;
 }
 
 /// Add a grayscale CSS filter to an SVG
@@ -255,27 +967,111 @@ fn add_grayscale_filter_to_svg(svg: String) -> String {
     }
 }
 
-async fn update_sd_column(channel: &MixerChannel, instance: &Instance) {
+pub async fn update_sd_column(channel: &MixerChannel, instance: &Instance, layout: ColumnLayout) {
+    if !is_audio_connected() {
+        show_disconnected_key(instance).await;
+        return;
+    }
+
     let Some(coords) = instance.coordinates else {
         return;
     };
 
-    match coords.row {
-        0 => {
+    match (coords.row, layout) {
+        (0, _) => {
             update_header(instance, channel).await;
         }
-        1 | 2 => {
+        (1..=3, _) if channel.is_placeholder => {
+            cleanup_sd_column(instance).await;
+        }
+        (1, ColumnLayout::Compact) => {
+            // Single key volume bar, short press raises and long press lowers the volume
+            if let Ok(img) = crate::gfx::get_volume_bar_data_uri_single(
+                channel.vol_percent,
+                get_volume_bar_max_percent(channel.vol_percent),
+                channel.is_input,
+                channel.level,
+            ) {
+                set_key_image(instance, img).await;
+            }
+        }
+        (1 | 2, ColumnLayout::Full) => {
             // Update volume buttons with bar graphics
-            if let Ok((upper_img, lower_img)) =
-                crate::gfx::get_volume_bar_data_uri_split(channel.vol_percent)
-            {
+            if let Ok((upper_img, lower_img)) = crate::gfx::get_volume_bar_data_uri_split(
+                channel.vol_percent,
+                get_volume_bar_max_percent(channel.vol_percent),
+                channel.is_input,
+                channel.level,
+            ) {
                 if coords.row == 1 {
-                    let _ = instance.set_image(Some(upper_img), None).await;
+                    set_key_image(instance, upper_img).await;
                 } else {
-                    let _ = instance.set_image(Some(lower_img), None).await;
+                    set_key_image(instance, lower_img).await;
                 }
             }
         }
+        (3, ColumnLayout::Full) => {
+            update_output_device_key(instance, channel).await;
+        }
         _ => {}
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn render(instance_id: &str, part: RenderPart, value: &str, ok: bool) -> bool {
+        send_render_part(instance_id, part, value.to_string(), |_| async move {
+            if ok { Ok(()) } else { Err(()) }
+        })
+        .await
+    }
+
+    #[test]
+    fn volume_keys_step_on_release_unless_pressed_together() {
+        let mut press_control = ButtonPressControl::new();
+
+        assert!(!press_control.press_volume_key(0, 1));
+        assert!(press_control.release_volume_key(0, 1));
+
+        // Both keys snap to the default volume, neither steps it afterwards
+        assert!(!press_control.press_volume_key(0, 2));
+        assert!(press_control.press_volume_key(0, 1));
+        assert!(!press_control.release_volume_key(0, 2));
+        assert!(!press_control.release_volume_key(0, 1));
+
+        // Keys of other columns don't count, nor do releases of keys never pressed
+        assert!(!press_control.press_volume_key(0, 1));
+        assert!(!press_control.press_volume_key(1, 2));
+        assert!(press_control.release_volume_key(1, 2));
+        assert!(press_control.release_volume_key(0, 1));
+        assert!(!press_control.release_volume_key(0, 1));
+    }
+
+    #[tokio::test]
+    async fn unchanged_parts_are_not_sent_again() {
+        let key = "render-test-unchanged";
+
+        assert!(render(key, RenderPart::Image, "volume-50", true).await);
+        assert!(!render(key, RenderPart::Image, "volume-50", true).await);
+        // Image and title are cached apart
+        assert!(render(key, RenderPart::Title, "volume-50", true).await);
+        assert!(render(key, RenderPart::Image, "volume-60", true).await);
+
+        // A key drawn from scratch gets everything again
+        forget_render_state(key).await;
+        assert!(render(key, RenderPart::Image, "volume-60", true).await);
+        assert!(render(key, RenderPart::Title, "volume-50", true).await);
+    }
+
+    #[tokio::test]
+    async fn failed_sends_are_tried_again() {
+        let key = "render-test-failed";
+
+        assert!(render(key, RenderPart::Image, "volume-50", true).await);
+        assert!(!render(key, RenderPart::Image, "volume-60", false).await);
+        assert!(render(key, RenderPart::Image, "volume-60", true).await);
+        assert!(!render(key, RenderPart::Image, "volume-60", true).await);
+    }
+}